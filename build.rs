@@ -0,0 +1,25 @@
+//! Embeds the git revision the benchmark was built from as `GIT_HASH`, so
+//! results can be traced to the measurement code that produced them. A
+//! `GIT_HASH` set in the build environment wins over asking git, for builds
+//! outside a checkout.
+
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=GIT_HASH");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+
+    let hash = std::env::var("GIT_HASH").ok().or_else(|| {
+        let output = Command::new("git")
+            .args(["rev-parse", "HEAD"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())?;
+        Some(String::from_utf8(output.stdout).ok()?.trim().to_string())
+    });
+    println!(
+        "cargo:rustc-env=GIT_HASH={}",
+        hash.unwrap_or_else(|| "unknown".to_string())
+    );
+}
@@ -0,0 +1,138 @@
+//! Scan accuracy against ground truth: dirty a recorded random set of pages,
+//! scan, and count the pages the scan got wrong either way. Category mask or
+//! resume bugs show up here long before they show up in timings.
+
+use crate::{pagemap, BenchArgs, MemoryRegion};
+use rand::rngs::StdRng;
+use rand::seq::index;
+use rand::SeedableRng;
+use serde::Serialize;
+
+#[derive(Serialize, Debug)]
+pub struct AccuracyResult {
+    pub total_size: usize,
+    pub dirty_fraction: f64,
+    pub written_pages: usize,
+    pub reported_pages: usize,
+
+    /// Pages reported dirty that were never written.
+    pub false_positives: usize,
+
+    /// Pages written that the scan didn't report.
+    pub false_negatives: usize,
+
+    /// Fraction of reported pages that were written; 1.0 if none were reported.
+    pub precision: f64,
+
+    /// Fraction of written pages that were reported; 1.0 if none were written.
+    pub recall: f64,
+    pub regions: usize,
+    pub scan_resumes: usize,
+}
+
+pub fn run(args: &BenchArgs, iterations: u64) -> anyhow::Result<Vec<AccuracyResult>> {
+    let page_size = rustix::param::page_size();
+    let mut rng = StdRng::seed_from_u64(args.seed);
+    let region = MemoryRegion::new(args, false)?;
+    region.ensure_writable()?;
+    let mut regions = args.scan_buffer();
+
+    let candidates: Vec<usize> = (0..args.total_size / page_size)
+        .filter(|&page| !region.is_guard_page(page))
+        .collect();
+    let dirty_count = (candidates.len() as f64 * args.dirty_fraction).round() as usize;
+
+    let results = (0..iterations)
+        .map(|_i| {
+            let mut written = vec![false; args.total_size.div_ceil(page_size)];
+            for i in index::sample(&mut rng, candidates.len(), dirty_count) {
+                let page = candidates[i];
+                written[page] = true;
+                unsafe { std::ptr::write_volatile(region.ptr.add(page * page_size), 1) };
+            }
+
+            let mut reported = vec![false; written.len()];
+            let mut region_count = 0;
+            let base = region.ptr as u64;
+            let scan_resumes = pagemap::for_each_dirty_region(
+                region.ptr,
+                args.total_size,
+                &mut regions,
+                &args.scan_categories,
+                args.max_scan_resumes,
+                |dirty_regions| {
+                    region_count += dirty_regions.len();
+                    for r in dirty_regions {
+                        let first = usize::try_from(r.start - base)? / page_size;
+                        let last = usize::try_from(r.end - base)?.div_ceil(page_size);
+                        reported[first..last].fill(true);
+                    }
+                    Ok(())
+                },
+            )?;
+
+            let count = |f: fn(bool, bool) -> bool| {
+                written
+                    .iter()
+                    .zip(&reported)
+                    .filter(|&(&w, &r)| f(w, r))
+                    .count()
+            };
+            let true_positives = count(|w, r| w && r);
+            let false_positives = count(|w, r| !w && r);
+            let false_negatives = count(|w, r| w && !r);
+            let ratio = |hits: usize, total: usize| {
+                if total == 0 {
+                    1.0
+                } else {
+                    hits as f64 / total as f64
+                }
+            };
+
+            // Drop the pages again so the next iteration starts unpopulated.
+            let ret = unsafe {
+                libc::madvise(
+                    region.ptr as *mut libc::c_void,
+                    args.total_size,
+                    libc::MADV_DONTNEED,
+                )
+            };
+            if ret != 0 {
+                return Err(std::io::Error::last_os_error().into());
+            }
+
+            Ok(AccuracyResult {
+                total_size: args.total_size,
+                dirty_fraction: args.dirty_fraction,
+                written_pages: dirty_count,
+                reported_pages: true_positives + false_positives,
+                false_positives,
+                false_negatives,
+                precision: ratio(true_positives, true_positives + false_positives),
+                recall: ratio(true_positives, dirty_count),
+                regions: region_count,
+                scan_resumes,
+            })
+        })
+        .collect();
+    region.release(args);
+    results
+}
+
+pub fn print_summary(results: &[AccuracyResult]) {
+    println!(
+        "{:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
+        "Written", "Reported", "False +", "False -", "Precision", "Recall"
+    );
+    for r in results {
+        println!(
+            "{:>10} {:>10} {:>10} {:>10} {:>10.4} {:>10.4}",
+            r.written_pages,
+            r.reported_pages,
+            r.false_positives,
+            r.false_negatives,
+            r.precision,
+            r.recall
+        );
+    }
+}
@@ -0,0 +1,58 @@
+//! Cold runs, on a freshly mapped region each iteration, against warm runs on
+//! one reused region. The difference in medians is what faulting the region
+//! in costs each strategy. Cold regions are never faulted in up front, not even
+//! for MemZero, which normally clears an already resident region.
+
+use crate::environment::Environment;
+use crate::stats::StrategySummary;
+use crate::{BenchError, Strategy};
+use serde::Serialize;
+
+#[derive(Serialize, Debug)]
+pub struct ColdWarm<'a> {
+    pub strategy: Strategy,
+    pub cold: &'a StrategySummary,
+    pub warm: &'a StrategySummary,
+
+    /// Cold median minus warm median, in nanoseconds. Negative if the warm
+    /// runs were slower.
+    pub fault_cost_ns: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ColdWarmOutput<'a> {
+    pub environment: &'a Environment,
+    pub strategies: &'a [ColdWarm<'a>],
+    pub errors: &'a [BenchError],
+}
+
+/// Pair up the cold and warm summaries of each strategy that has both.
+pub fn report<'a>(cold: &'a [StrategySummary], warm: &'a [StrategySummary]) -> Vec<ColdWarm<'a>> {
+    cold.iter()
+        .filter_map(|cold| {
+            let warm = warm.iter().find(|w| w.strategy == cold.strategy)?;
+            Some(ColdWarm {
+                strategy: cold.strategy,
+                cold,
+                warm,
+                fault_cost_ns: cold.median.as_nanos() as f64 - warm.median.as_nanos() as f64,
+            })
+        })
+        .collect()
+}
+
+pub fn print_report(report: &[ColdWarm]) {
+    println!(
+        "{:<16} {:>16} {:>16} {:>16}",
+        "Strategy", "Cold median (us)", "Warm median (us)", "Fault cost (us)"
+    );
+    for r in report {
+        println!(
+            "{:<16} {:>16.2} {:>16.2} {:>16.2}",
+            format!("{:?}", r.strategy),
+            r.cold.median.as_nanos() as f64 / 1000.0,
+            r.warm.median.as_nanos() as f64 / 1000.0,
+            r.fault_cost_ns / 1000.0
+        );
+    }
+}
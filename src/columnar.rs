@@ -0,0 +1,240 @@
+//! Parquet output of the flat `BenchResult` rows, for large sweep datasets.
+
+use crate::BenchResult;
+use arrow_array::{
+    ArrayRef, BooleanArray, DurationNanosecondArray, Float64Array, RecordBatch, StringArray,
+    UInt64Array,
+};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use clap::ValueEnum;
+use parquet::arrow::ArrowWriter;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub fn write_parquet(path: &Path, results: &[BenchResult]) -> anyhow::Result<()> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("strategy", DataType::Utf8, false),
+        Field::new("thread", DataType::UInt64, false),
+        Field::new("iteration", DataType::UInt64, false),
+        Field::new("kernel", DataType::Utf8, false),
+        Field::new("total_size", DataType::UInt64, false),
+        Field::new("dirty_fraction", DataType::Float64, false),
+        Field::new("duration", DataType::Duration(TimeUnit::Nanosecond), false),
+        Field::new("map", DataType::Duration(TimeUnit::Nanosecond), true),
+        Field::new("dirty", DataType::Duration(TimeUnit::Nanosecond), true),
+        Field::new("scan", DataType::Duration(TimeUnit::Nanosecond), true),
+        Field::new("clear", DataType::Duration(TimeUnit::Nanosecond), true),
+        Field::new("refault", DataType::Duration(TimeUnit::Nanosecond), true),
+        Field::new("unmap", DataType::Duration(TimeUnit::Nanosecond), true),
+        Field::new("threads", DataType::UInt64, false),
+        Field::new("processes", DataType::UInt64, false),
+        Field::new("prot", DataType::Utf8, false),
+        Field::new("touch_order", DataType::Utf8, false),
+        Field::new("dirty_passes", DataType::UInt64, false),
+        Field::new("seed", DataType::UInt64, false),
+        Field::new("age", DataType::Duration(TimeUnit::Nanosecond), true),
+        Field::new("dirty_run", DataType::UInt64, true),
+        Field::new("guard_interval", DataType::UInt64, true),
+        Field::new("huge_pages", DataType::Boolean, false),
+        Field::new(
+            "background_duration",
+            DataType::Duration(TimeUnit::Nanosecond),
+            true,
+        ),
+        Field::new(
+            "cycle_duration",
+            DataType::Duration(TimeUnit::Nanosecond),
+            true,
+        ),
+        Field::new("scan_regions", DataType::UInt64, true),
+        Field::new("scan_resumes", DataType::UInt64, true),
+        Field::new("scan_pages_walked", DataType::UInt64, true),
+        Field::new("scan_pages_matched", DataType::UInt64, true),
+        Field::new("scan_pages_per_sec", DataType::Float64, true),
+        Field::new("scan_regions_per_sec", DataType::Float64, true),
+        Field::new("clear_method", DataType::Utf8, true),
+        Field::new("bytes_cleared", DataType::UInt64, true),
+        Field::new("bytes_skipped", DataType::UInt64, true),
+        Field::new("thp_split", DataType::Boolean, true),
+        Field::new("thp_pages_split", DataType::UInt64, true),
+        Field::new("cycles", DataType::UInt64, true),
+    ]));
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(
+            results.iter().map(|r| format!("{:?}", r.strategy)),
+        )),
+        Arc::new(UInt64Array::from_iter_values(
+            results.iter().map(|r| r.thread as u64),
+        )),
+        Arc::new(UInt64Array::from_iter_values(
+            results.iter().map(|r| r.iteration as u64),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            results.iter().map(|r| r.kernel.as_str()),
+        )),
+        Arc::new(UInt64Array::from_iter_values(
+            results.iter().map(|r| r.total_size as u64),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            results.iter().map(|r| r.dirty_fraction),
+        )),
+        Arc::new(DurationNanosecondArray::from_iter_values(
+            results.iter().map(|r| r.duration.as_nanos() as i64),
+        )),
+        phase_column(results, |r| r.phases.map),
+        phase_column(results, |r| r.phases.dirty),
+        phase_column(results, |r| r.phases.scan),
+        phase_column(results, |r| r.phases.clear),
+        phase_column(results, |r| r.phases.refault),
+        phase_column(results, |r| r.phases.unmap),
+        Arc::new(UInt64Array::from_iter_values(
+            results.iter().map(|r| r.threads as u64),
+        )),
+        Arc::new(UInt64Array::from_iter_values(
+            results.iter().map(|r| r.processes as u64),
+        )),
+        Arc::new(StringArray::from_iter(results.iter().map(|r| {
+            r.prot.to_possible_value().map(|v| v.get_name().to_string())
+        }))),
+        Arc::new(StringArray::from_iter(results.iter().map(|r| {
+            r.touch_order
+                .to_possible_value()
+                .map(|v| v.get_name().to_string())
+        }))),
+        Arc::new(UInt64Array::from_iter_values(
+            results.iter().map(|r| r.dirty_passes as u64),
+        )),
+        Arc::new(UInt64Array::from_iter_values(
+            results.iter().map(|r| r.seed),
+        )),
+        phase_column(results, |r| r.age),
+        Arc::new(UInt64Array::from_iter(
+            results.iter().map(|r| r.dirty_run.map(|n| n as u64)),
+        )),
+        Arc::new(UInt64Array::from_iter(
+            results.iter().map(|r| r.guard_interval.map(|n| n as u64)),
+        )),
+        Arc::new(BooleanArray::from_iter(
+            results.iter().map(|r| Some(r.huge_pages)),
+        )),
+        Arc::new(DurationNanosecondArray::from_iter(
+            results
+                .iter()
+                .map(|r| r.background_duration.map(|d| d.as_nanos() as i64)),
+        )),
+        phase_column(results, |r| r.cycle_duration),
+        Arc::new(UInt64Array::from_iter(
+            results.iter().map(|r| r.scan_regions.map(|n| n as u64)),
+        )),
+        Arc::new(UInt64Array::from_iter(
+            results.iter().map(|r| r.scan_resumes.map(|n| n as u64)),
+        )),
+        Arc::new(UInt64Array::from_iter(
+            results
+                .iter()
+                .map(|r| r.scan_pages_walked.map(|n| n as u64)),
+        )),
+        Arc::new(UInt64Array::from_iter(
+            results
+                .iter()
+                .map(|r| r.scan_pages_matched.map(|n| n as u64)),
+        )),
+        Arc::new(Float64Array::from_iter(
+            results.iter().map(|r| r.scan_pages_per_sec),
+        )),
+        Arc::new(Float64Array::from_iter(
+            results.iter().map(|r| r.scan_regions_per_sec),
+        )),
+        Arc::new(StringArray::from_iter(results.iter().map(|r| {
+            r.clear_method
+                .and_then(|m| m.to_possible_value())
+                .map(|v| v.get_name().to_string())
+        }))),
+        Arc::new(UInt64Array::from_iter(
+            results.iter().map(|r| r.bytes_cleared.map(|n| n as u64)),
+        )),
+        Arc::new(UInt64Array::from_iter(
+            results.iter().map(|r| r.bytes_skipped.map(|n| n as u64)),
+        )),
+        Arc::new(BooleanArray::from_iter(results.iter().map(|r| r.thp_split))),
+        Arc::new(UInt64Array::from_iter(
+            results.iter().map(|r| r.thp_pages_split.map(|n| n as u64)),
+        )),
+        Arc::new(UInt64Array::from_iter(results.iter().map(|r| r.cycles))),
+    ];
+    let batch = RecordBatch::try_new(schema.clone(), columns)?;
+
+    let mut writer = ArrowWriter::try_new(File::create(path)?, schema, None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+fn phase_column(results: &[BenchResult], phase: fn(&BenchResult) -> Option<Duration>) -> ArrayRef {
+    Arc::new(DurationNanosecondArray::from_iter(
+        results
+            .iter()
+            .map(|r| phase(r).map(|d| d.as_nanos() as i64)),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::write_parquet;
+    use crate::{BenchResult, ClearMethod, Phases, Protection, Strategy, TouchOrder};
+    use std::time::Duration;
+
+    #[test]
+    fn columns_match_the_schema() {
+        let ms = Duration::from_millis(1);
+        let result = BenchResult {
+            strategy: Strategy::PagemapScan,
+            thread: 0,
+            iteration: 0,
+            kernel: "6.18.0".to_string(),
+            total_size: 1 << 20,
+            dirty_fraction: 0.5,
+            duration: ms,
+            phases: Phases {
+                map: Some(ms),
+                dirty: Some(ms),
+                scan: Some(ms),
+                clear: Some(ms),
+                refault: Some(ms),
+                unmap: Some(ms),
+            },
+            threads: 1,
+            processes: 1,
+            prot: Protection::Rw,
+            touch_order: TouchOrder::Ascending,
+            dirty_passes: 1,
+            seed: 0,
+            age: Some(ms),
+            dirty_run: Some(4),
+            guard_interval: Some(16),
+            huge_pages: false,
+            background_duration: Some(ms),
+            cycle_duration: Some(ms),
+            scan_regions: Some(1),
+            clear_method: Some(ClearMethod::Memset),
+            bytes_cleared: Some(1 << 19),
+            bytes_skipped: Some(0),
+            thp_split: Some(false),
+            thp_pages_split: Some(0),
+            scan_resumes: Some(0),
+            scan_pages_walked: Some(256),
+            scan_pages_matched: Some(128),
+            scan_pages_per_sec: Some(1e6),
+            scan_regions_per_sec: Some(1e3),
+            cycles: Some(1000),
+        };
+        let path =
+            std::env::temp_dir().join(format!("pagemap-scan-{}.parquet", std::process::id()));
+        let written = write_parquet(&path, &[result]);
+        let _ = std::fs::remove_file(&path);
+        written.unwrap();
+    }
+}
@@ -0,0 +1,99 @@
+//! Comparison of saved JSON results across kernels, to see which kernel
+//! handles each strategy best.
+
+use crate::stats::percentile;
+use serde::Deserialize;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// The subset of a saved result row that the comparison needs.
+#[derive(Deserialize, Debug)]
+struct SavedResult {
+    strategy: String,
+    kernel: String,
+    #[serde(deserialize_with = "crate::duration_format::deserialize")]
+    duration: Duration,
+}
+
+#[derive(Deserialize, Debug)]
+struct SavedOutput {
+    results: Vec<SavedResult>,
+}
+
+/// Print the median duration of each strategy on each kernel across the
+/// `--json` outputs in `paths`, in the order kernels and strategies first
+/// appear.
+pub fn compare_kernels(paths: &[PathBuf]) -> anyhow::Result<()> {
+    let mut results: Vec<SavedResult> = Vec::new();
+    for path in paths {
+        let output: SavedOutput = serde_json::from_str(&fs::read_to_string(path)?)
+            .map_err(|e| anyhow::anyhow!("{}: not a JSON results file: {}", path.display(), e))?;
+        results.extend(output.results);
+    }
+
+    println!(
+        "{:<48} {:<16} {:>8} {:>12}",
+        "Kernel", "Strategy", "Samples", "Median (us)"
+    );
+    for (kernel, strategy) in groups(&results) {
+        let mut samples: Vec<f64> = results
+            .iter()
+            .filter(|r| r.kernel == kernel && r.strategy == strategy)
+            .map(|r| r.duration.as_nanos() as f64)
+            .collect();
+        samples.sort_by(f64::total_cmp);
+        println!(
+            "{:<48} {:<16} {:>8} {:>12.2}",
+            kernel,
+            strategy,
+            samples.len(),
+            percentile(&samples, 0.5) / 1000.0
+        );
+    }
+    Ok(())
+}
+
+/// Each distinct kernel and strategy pair in `results`, in the order the pair
+/// first appears.
+fn groups(results: &[SavedResult]) -> Vec<(&str, &str)> {
+    let mut groups: Vec<(&str, &str)> = Vec::new();
+    for r in results {
+        if !groups.contains(&(&r.kernel, &r.strategy)) {
+            groups.push((&r.kernel, &r.strategy));
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{groups, SavedResult};
+    use std::time::Duration;
+
+    fn saved(kernel: &str, strategy: &str) -> SavedResult {
+        SavedResult {
+            strategy: strategy.to_string(),
+            kernel: kernel.to_string(),
+            duration: Duration::from_micros(1),
+        }
+    }
+
+    #[test]
+    fn groups_keep_the_order_kernels_first_appear() {
+        let results = [
+            saved("6.9.0", "Madvise"),
+            saved("6.10.0", "Madvise"),
+            saved("6.9.0", "MemZero"),
+            saved("6.10.0", "Madvise"),
+        ];
+        assert_eq!(
+            groups(&results),
+            [
+                ("6.9.0", "Madvise"),
+                ("6.10.0", "Madvise"),
+                ("6.9.0", "MemZero")
+            ]
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Scanning with `PAGEMAP_SCAN`'s write-protect mode while another thread keeps
+//! dirtying the region, as a guest does during live migration.
+
+use crate::stats::percentile;
+use crate::uffd::WriteProtect;
+use crate::{pagemap, BenchArgs, MemoryRegion};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Serialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Nothing writes to the region during the scan.
+    Quiescent,
+    /// A writer thread dirties the region throughout the scan.
+    Concurrent,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ConcurrentResult {
+    pub mode: Mode,
+    pub total_size: usize,
+    pub dirty_fraction: f64,
+    pub duration: Duration,
+    pub regions: usize,
+    pub dirty_pages: usize,
+
+    /// Whether the reported regions were sorted, disjoint and confined to the
+    /// pages the writer touches.
+    pub consistent: bool,
+}
+
+pub fn run(args: &BenchArgs, iterations: u64) -> anyhow::Result<Vec<ConcurrentResult>> {
+    let page_size = rustix::param::page_size();
+    let dirty_bytes = (args.total_size as f64 * args.dirty_fraction).round() as usize;
+
+    let mut region = MemoryRegion::new(args, false)?;
+    let _wp = WriteProtect::register(region.ptr, args.total_size)?;
+    let mut regions = args.scan_buffer();
+
+    // The writer can't borrow the region, so hand it the addresses to write.
+    let base = region.ptr as usize;
+    let written: Vec<usize> = (0..dirty_bytes.div_ceil(page_size))
+        .filter(|&page| !region.is_guard_page(page))
+        .map(|page| base + page * page_size)
+        .collect();
+    let dirty_end = (base + dirty_bytes.div_ceil(page_size) * page_size) as u64;
+
+    let mut scan = |mode: Mode, region: &MemoryRegion| -> anyhow::Result<ConcurrentResult> {
+        let mut region_count = 0;
+        let mut dirty = 0;
+        let mut consistent = true;
+        let mut prev_end = base as u64;
+        let start = Instant::now();
+        pagemap::for_each_dirty_region_and_reset(
+            region.ptr,
+            args.total_size,
+            &mut regions,
+            &args.scan_categories,
+            args.max_scan_resumes,
+            |dirty_regions| {
+                region_count += dirty_regions.len();
+                for r in dirty_regions {
+                    consistent &= r.start >= prev_end && r.start < r.end && r.end <= dirty_end;
+                    prev_end = r.end;
+                    dirty += r.end - r.start;
+                }
+                Ok(())
+            },
+        )?;
+        let duration = start.elapsed();
+        Ok(ConcurrentResult {
+            mode,
+            total_size: args.total_size,
+            dirty_fraction: args.dirty_fraction,
+            duration,
+            regions: region_count,
+            dirty_pages: dirty as usize / page_size,
+            consistent,
+        })
+    };
+
+    // Start write-protected.
+    scan(Mode::Quiescent, &region)?;
+    let mut results = Vec::new();
+    for _ in 0..iterations {
+        region.dirty_prefix(dirty_bytes)?;
+        results.push(scan(Mode::Quiescent, &region)?);
+    }
+
+    let stop = AtomicBool::new(false);
+    let concurrent = thread::scope(|s| {
+        let writer = s.spawn(|| {
+            let mut value = 0u8;
+            while !stop.load(Ordering::Relaxed) {
+                value = value.wrapping_add(1);
+                for &addr in &written {
+                    unsafe { std::ptr::write_volatile(addr as *mut u8, value) };
+                }
+            }
+        });
+        let scans = (0..iterations)
+            .map(|_i| scan(Mode::Concurrent, &region))
+            .collect::<anyhow::Result<Vec<_>>>();
+        stop.store(true, Ordering::Relaxed);
+        let _ = writer.join();
+        scans
+    })?;
+    results.extend(concurrent);
+
+    region.release(args);
+    Ok(results)
+}
+
+pub fn print_summary(results: &[ConcurrentResult]) {
+    println!(
+        "{:<12} {:>12} {:>12} {:>12} {:>12} {:>14}",
+        "Mode", "p50 (us)", "p90 (us)", "p99 (us)", "Regions", "Inconsistent"
+    );
+    for mode in [Mode::Quiescent, Mode::Concurrent] {
+        let runs: Vec<&ConcurrentResult> = results.iter().filter(|r| r.mode == mode).collect();
+        if runs.is_empty() {
+            continue;
+        }
+        let mut durations: Vec<f64> = runs.iter().map(|r| r.duration.as_nanos() as f64).collect();
+        durations.sort_by(f64::total_cmp);
+        let regions = runs.iter().map(|r| r.regions as f64).sum::<f64>() / runs.len() as f64;
+        println!(
+            "{:<12} {:>12.2} {:>12.2} {:>12.2} {:>12.1} {:>14}",
+            format!("{:?}", mode),
+            percentile(&durations, 0.5) / 1000.0,
+            percentile(&durations, 0.9) / 1000.0,
+            percentile(&durations, 0.99) / 1000.0,
+            regions,
+            runs.iter().filter(|r| !r.consistent).count()
+        );
+    }
+}
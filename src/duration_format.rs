@@ -0,0 +1,80 @@
+//! How JSON output writes each result's `duration`, chosen once per run with
+//! `--duration-format`.
+
+use clap::ValueEnum;
+use serde::{Deserialize, Deserializer, Serializer};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+#[derive(ValueEnum, Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum DurationFormat {
+    /// serde's `{"secs": .., "nanos": ..}` object
+    #[default]
+    Struct,
+    /// Integer nanoseconds
+    Nanos,
+    /// An ISO 8601 duration in seconds, e.g. `PT0.00123S`
+    Iso8601,
+}
+
+static FORMAT: OnceLock<DurationFormat> = OnceLock::new();
+
+/// Set the format for the rest of the run. Later calls are ignored.
+pub fn set(format: DurationFormat) {
+    let _ = FORMAT.set(format);
+}
+
+/// `serialize_with` for durations written in the run's format.
+pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    match FORMAT.get().copied().unwrap_or_default() {
+        DurationFormat::Struct => serde::Serialize::serialize(duration, serializer),
+        DurationFormat::Nanos => serializer.serialize_u128(duration.as_nanos()),
+        DurationFormat::Iso8601 => serializer.serialize_str(&iso8601(*duration)),
+    }
+}
+
+/// `deserialize_with` accepting a duration written in any of the formats.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Saved {
+        Struct { secs: u64, nanos: u32 },
+        Nanos(u64),
+        Iso8601(String),
+    }
+    match Saved::deserialize(deserializer)? {
+        Saved::Struct { secs, nanos } => Ok(Duration::new(secs, nanos)),
+        Saved::Nanos(nanos) => Ok(Duration::from_nanos(nanos)),
+        Saved::Iso8601(text) => parse_iso8601(&text)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid duration: {}", text))),
+    }
+}
+
+/// Seconds only, with trailing zeros dropped: `PT0.00123S`, `PT2S`.
+fn iso8601(duration: Duration) -> String {
+    let nanos = duration.subsec_nanos();
+    if nanos == 0 {
+        return format!("PT{}S", duration.as_secs());
+    }
+    let fraction = format!("{:09}", nanos);
+    format!(
+        "PT{}.{}S",
+        duration.as_secs(),
+        fraction.trim_end_matches('0')
+    )
+}
+
+/// Parse the seconds-only form [`iso8601`] writes.
+fn parse_iso8601(text: &str) -> Option<Duration> {
+    let seconds = text.strip_prefix("PT")?.strip_suffix('S')?;
+    let (secs, fraction) = seconds.split_once('.').unwrap_or((seconds, ""));
+    if fraction.len() > 9 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let nanos = if fraction.is_empty() {
+        0
+    } else {
+        format!("{:0<9}", fraction).parse().ok()?
+    };
+    Some(Duration::new(secs.parse().ok()?, nanos))
+}
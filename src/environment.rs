@@ -0,0 +1,43 @@
+//! The machine a run was taken on, recorded so results from a fleet of
+//! machines can be grouped and compared.
+
+use serde::Serialize;
+use std::sync::LazyLock;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Environment {
+    /// Full kernel version: the release followed by the build version string.
+    pub kernel: String,
+    pub machine: String,
+    pub page_size: usize,
+
+    /// Git revision of the benchmark build, or `unknown` if it was built
+    /// outside a checkout without `GIT_HASH` set.
+    pub git_hash: String,
+}
+
+impl Environment {
+    pub fn capture() -> Self {
+        let uname = rustix::system::uname();
+        Environment {
+            kernel: kernel_version().to_string(),
+            machine: uname.machine().to_string_lossy().into_owned(),
+            page_size: rustix::param::page_size(),
+            git_hash: env!("GIT_HASH").to_string(),
+        }
+    }
+}
+
+static KERNEL_VERSION: LazyLock<String> = LazyLock::new(|| {
+    let uname = rustix::system::uname();
+    format!(
+        "{} {}",
+        uname.release().to_string_lossy(),
+        uname.version().to_string_lossy()
+    )
+});
+
+/// The full kernel version, as recorded in [`Environment::kernel`].
+pub fn kernel_version() -> &'static str {
+    &KERNEL_VERSION
+}
@@ -0,0 +1,200 @@
+//! Scanning another process: run a command, wait for it to signal that its
+//! memory is set up, then scan its writable mappings through its
+//! `/proc/<pid>/pagemap`.
+//!
+//! The child must honor this readiness contract, or the scan races its setup
+//! and finds nothing:
+//!
+//! - It inherits an eventfd whose descriptor number is in the
+//!   `PAGEMAP_SCAN_READY_FD` environment variable.
+//! - Once it has mapped and written the memory to be scanned, it writes a
+//!   nonzero 8-byte native-endian integer to that descriptor, as
+//!   `eventfd_write(fd, 1)` does. It may close the descriptor afterwards.
+//! - It then leaves that memory alone and keeps running until it's killed
+//!   with `SIGKILL`, which happens once all iterations have scanned it.
+//!
+//! Exiting before signalling, or not signalling within `--exec-timeout`, fails
+//! the run.
+
+use crate::maps::{self, MapEntry};
+use crate::stats::percentile;
+use crate::{pagemap, BenchArgs};
+use rustix::event::{eventfd, poll, EventfdFlags, PollFd, PollFlags, Timespec};
+use rustix::fd::{AsRawFd, OwnedFd};
+use rustix::io::Errno;
+use serde::Serialize;
+use std::process::{Child, Command};
+use std::time::{Duration, Instant};
+
+/// The environment variable holding the readiness eventfd's number.
+pub const READY_FD_ENV: &str = "PAGEMAP_SCAN_READY_FD";
+
+/// How often to check whether the child exited while waiting for readiness.
+const EXIT_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Serialize, Debug)]
+pub struct ExecResult {
+    pub pid: u32,
+
+    /// How long the child took to signal readiness after being spawned.
+    pub ready_after: Duration,
+
+    /// Writable mappings scanned, and their total size.
+    pub mappings: usize,
+    pub mapped_bytes: usize,
+
+    pub regions: usize,
+    pub dirty_bytes: usize,
+    pub duration: Duration,
+}
+
+/// Kills and reaps the child when dropped, including on errors.
+struct KillOnDrop(Child);
+
+impl Drop for KillOnDrop {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+pub fn run(
+    args: &BenchArgs,
+    command: &[String],
+    timeout: Duration,
+    iterations: u64,
+) -> anyhow::Result<Vec<ExecResult>> {
+    let (program, program_args) = command
+        .split_first()
+        .ok_or_else(|| anyhow::anyhow!("--exec needs a command to run"))?;
+
+    // Without CLOEXEC, so the child inherits it.
+    let ready = eventfd(0, EventfdFlags::empty())?;
+    let spawned = Instant::now();
+    let mut child = KillOnDrop(
+        Command::new(program)
+            .args(program_args)
+            .env(READY_FD_ENV, ready.as_raw_fd().to_string())
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("Can't run {}: {}", program, e))?,
+    );
+    let pid = child.0.id();
+    wait_ready(&mut child.0, &ready, timeout)?;
+    let ready_after = spawned.elapsed();
+
+    let pagemap = pagemap::open_process_pagemap(pid)?;
+    let mappings: Vec<MapEntry> = maps::read_maps(Some(pid))?
+        .into_iter()
+        .filter(is_scannable)
+        .collect();
+    let mapped_bytes = mappings.iter().map(|m| (m.end - m.start) as usize).sum();
+    let mut regions = args.scan_buffer();
+
+    (0..iterations)
+        .map(|_i| {
+            let mut region_count = 0;
+            let mut dirty_bytes = 0;
+            let start = Instant::now();
+            for mapping in &mappings {
+                pagemap::for_each_dirty_region_in(
+                    &pagemap,
+                    mapping.start as *const u8,
+                    (mapping.end - mapping.start) as usize,
+                    &mut regions,
+                    &args.scan_categories,
+                    args.max_scan_resumes,
+                    |dirty_regions| {
+                        region_count += dirty_regions.len();
+                        dirty_bytes += dirty_regions.iter().map(|r| r.end - r.start).sum::<u64>();
+                        Ok(())
+                    },
+                )?;
+            }
+            let duration = start.elapsed();
+            Ok(ExecResult {
+                pid,
+                ready_after,
+                mappings: mappings.len(),
+                mapped_bytes,
+                regions: region_count,
+                dirty_bytes: dirty_bytes as usize,
+                duration,
+            })
+        })
+        .collect()
+}
+
+/// Block until the child writes to `ready`, failing if it exits first or
+/// `timeout` passes.
+fn wait_ready(child: &mut Child, ready: &OwnedFd, timeout: Duration) -> anyhow::Result<()> {
+    let start = Instant::now();
+    loop {
+        let remaining = timeout.saturating_sub(start.elapsed());
+        if remaining.is_zero() {
+            return Err(anyhow::anyhow!(
+                "--exec child {} didn't signal readiness on ${} within {:?}",
+                child.id(),
+                READY_FD_ENV,
+                timeout
+            ));
+        }
+
+        let mut fds = [PollFd::new(ready, PollFlags::IN)];
+        let wait = Timespec::try_from(remaining.min(EXIT_CHECK_INTERVAL))?;
+        match poll(&mut fds, Some(&wait)) {
+            Ok(0) | Err(Errno::INTR) => {}
+            Ok(_) => {
+                let mut count = [0u8; 8];
+                rustix::io::read(ready, &mut count)?;
+                return Ok(());
+            }
+            Err(e) => return Err(anyhow::anyhow!("poll on the readiness eventfd: {}", e)),
+        }
+
+        if let Some(status) = child.try_wait()? {
+            return Err(anyhow::anyhow!(
+                "--exec child {} exited ({}) before signalling readiness on ${}",
+                child.id(),
+                status,
+                READY_FD_ENV
+            ));
+        }
+    }
+}
+
+/// Writable mappings, leaving out the kernel's special ones, which
+/// can't be scanned.
+fn is_scannable(mapping: &MapEntry) -> bool {
+    mapping.writable && !matches!(mapping.pathname.as_deref(), Some("[vsyscall]" | "[vvar]"))
+}
+
+pub fn print_summary(results: &[ExecResult]) {
+    let Some(first) = results.first() else {
+        return;
+    };
+    println!(
+        "Child {} ready after {:.2} ms; scanning {} mappings ({:.2} MiB)",
+        first.pid,
+        first.ready_after.as_secs_f64() * 1000.0,
+        first.mappings,
+        first.mapped_bytes as f64 / (1024.0 * 1024.0)
+    );
+    let mut durations: Vec<f64> = results
+        .iter()
+        .map(|r| r.duration.as_nanos() as f64)
+        .collect();
+    durations.sort_by(f64::total_cmp);
+    let mean = |f: fn(&ExecResult) -> usize| {
+        results.iter().map(|r| f(r) as f64).sum::<f64>() / results.len() as f64
+    };
+    println!(
+        "{:>14} {:>14} {:>16}",
+        "Median (us)", "Regions", "Dirty (MiB)"
+    );
+    println!(
+        "{:>14.2} {:>14.1} {:>16.2}",
+        percentile(&durations, 0.5) / 1000.0,
+        mean(|r| r.regions),
+        mean(|r| r.dirty_bytes) / (1024.0 * 1024.0)
+    );
+}
@@ -0,0 +1,66 @@
+//! A stable hash of the parts of a run that don't depend on timing, so two runs
+//! can be confirmed comparable before their durations are diffed.
+
+use crate::environment::Environment;
+use crate::{BenchArgs, BenchError, BenchResult};
+use serde_json::{json, Value};
+
+/// Fields of a serialized [`BenchResult`] that are measurements rather than
+/// structure, and so are left out of the hash.
+const TIMING_FIELDS: &[&str] = &[
+    "duration",
+    "phases",
+    "background_duration",
+    "cycle_duration",
+    "cycles",
+    "scan_pages_per_sec",
+    "scan_regions_per_sec",
+];
+
+pub const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Hash the configuration, environment, dirty pattern and per-result region
+/// counts of a run. Re-running with the same seed and flags on the same
+/// machine gives the same hash.
+pub fn structural_hash(
+    args: &BenchArgs,
+    environment: &Environment,
+    results: &[BenchResult],
+    errors: &[BenchError],
+) -> anyhow::Result<u64> {
+    let mut results = serde_json::to_value(results)?;
+    if let Value::Array(results) = &mut results {
+        for result in results.iter_mut().filter_map(Value::as_object_mut) {
+            for field in TIMING_FIELDS {
+                result.remove(*field);
+            }
+        }
+    }
+    let failed: Vec<Value> = errors
+        .iter()
+        .map(|e| json!({ "strategy": e.strategy, "thread": e.thread }))
+        .collect();
+
+    // `json!` objects keep their keys sorted, so the encoding is canonical.
+    let structure = json!({
+        "environment": environment,
+        "seed": args.seed,
+        "scan_categories": format!("{:?}", args.scan_categories),
+        "results": results,
+        "failed": failed,
+    });
+    Ok(fnv1a(serde_json::to_string(&structure)?.as_bytes()))
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    fnv1a_extend(FNV_OFFSET_BASIS, bytes)
+}
+
+/// Fold `bytes` into a running FNV-1a `hash`, which starts at
+/// [`FNV_OFFSET_BASIS`].
+pub fn fnv1a_extend(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(FNV_PRIME)
+    })
+}
@@ -0,0 +1,124 @@
+//! Incremental dirty tracking across multiple write phases, modelling the
+//! delta scans of live migration with `PAGEMAP_SCAN`'s write-protect mode.
+
+use crate::stats::percentile;
+use crate::uffd::WriteProtect;
+use crate::{pagemap, BenchArgs, MemoryRegion};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+#[derive(Serialize, Debug)]
+pub struct ScanPhase {
+    pub duration: Duration,
+    pub regions: usize,
+    pub dirty_pages: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct IncrementalResult {
+    pub total_size: usize,
+    pub dirty_fraction: f64,
+    pub phase2_fraction: f64,
+    pub dirty_passes: usize,
+    pub threads: usize,
+    pub processes: usize,
+
+    /// Scan after dirtying `dirty_fraction` of the region.
+    pub phase1: ScanPhase,
+
+    /// Scan after rewriting `phase2_fraction` of the phase 1 pages; only these
+    /// pages should be reported.
+    pub phase2: ScanPhase,
+}
+
+pub fn run(
+    args: &BenchArgs,
+    iterations: u64,
+    phase2_fraction: f64,
+) -> anyhow::Result<Vec<IncrementalResult>> {
+    let page_size = rustix::param::page_size();
+    let phase1_bytes = (args.total_size as f64 * args.dirty_fraction).round() as usize;
+    let phase2_bytes = (phase1_bytes as f64 * phase2_fraction).round() as usize;
+
+    let mut region = MemoryRegion::new(args, false)?;
+    let _wp = WriteProtect::register(region.ptr, args.total_size)?;
+    let mut regions = args.scan_buffer();
+
+    let mut scan = |region: &MemoryRegion| -> anyhow::Result<ScanPhase> {
+        let mut region_count = 0;
+        let mut dirty_bytes = 0;
+        let start = Instant::now();
+        pagemap::for_each_dirty_region_and_reset(
+            region.ptr,
+            args.total_size,
+            &mut regions,
+            &args.scan_categories,
+            args.max_scan_resumes,
+            |dirty_regions| {
+                region_count += dirty_regions.len();
+                dirty_bytes += dirty_regions.iter().map(|r| r.end - r.start).sum::<u64>();
+                Ok(())
+            },
+        )?;
+        let duration = start.elapsed();
+        Ok(ScanPhase {
+            duration,
+            regions: region_count,
+            dirty_pages: dirty_bytes as usize / page_size,
+        })
+    };
+
+    let results = (0..iterations)
+        .map(|_i| {
+            // Re-arm write protection so each iteration starts from a clean slate.
+            scan(&region)?;
+
+            // Each extra pass re-dirties pages the previous scan write-protected.
+            for _ in 0..args.dirty_passes {
+                region.dirty_prefix(phase1_bytes)?;
+                scan(&region)?;
+            }
+
+            region.dirty_prefix(phase1_bytes)?;
+            let phase1 = scan(&region)?;
+            region.dirty_prefix(phase2_bytes)?;
+            let phase2 = scan(&region)?;
+
+            Ok(IncrementalResult {
+                total_size: args.total_size,
+                dirty_fraction: args.dirty_fraction,
+                phase2_fraction,
+                dirty_passes: args.dirty_passes,
+                threads: args.threads,
+                processes: args.processes,
+                phase1,
+                phase2,
+            })
+        })
+        .collect();
+    region.release(args);
+    results
+}
+
+pub fn print_summary(results: &[IncrementalResult]) {
+    println!(
+        "{:<8} {:>14} {:>14} {:>14}",
+        "Phase", "Median (us)", "Regions", "Dirty pages"
+    );
+    let phase1: Vec<&ScanPhase> = results.iter().map(|r| &r.phase1).collect();
+    let phase2: Vec<&ScanPhase> = results.iter().map(|r| &r.phase2).collect();
+    for (name, phase) in [("1", phase1), ("2", phase2)] {
+        let mut durations: Vec<f64> = phase.iter().map(|p| p.duration.as_nanos() as f64).collect();
+        durations.sort_by(f64::total_cmp);
+        let mean = |f: fn(&ScanPhase) -> usize| {
+            phase.iter().map(|p| f(p) as f64).sum::<f64>() / phase.len() as f64
+        };
+        println!(
+            "{:<8} {:>14.2} {:>14.1} {:>14.1}",
+            name,
+            percentile(&durations, 0.5) / 1000.0,
+            mean(|p| p.regions),
+            mean(|p| p.dirty_pages)
+        );
+    }
+}
@@ -1,16 +1,59 @@
-use clap::Parser;
-use nix::sys::mman::{mmap_anonymous, MapFlags, ProtFlags};
+use clap::{Parser, ValueEnum};
+use nix::sched::{sched_setaffinity, CpuSet};
+use nix::sys::mman::{mmap_anonymous, mprotect, MapFlags, ProtFlags};
+use nix::unistd::Pid;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
 use rayon::prelude::*;
 use serde::Serialize;
+use std::fs::File;
 use std::marker::PhantomData;
-use std::mem::MaybeUninit;
+use std::mem::{self, MaybeUninit};
+use std::num::NonZeroUsize;
+use std::os::fd::AsRawFd;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr::NonNull;
 use std::slice;
+use std::sync::mpsc;
+use std::thread;
 use std::time::{Duration, Instant};
 
+mod accuracy;
+mod cold_warm;
+#[cfg(feature = "parquet")]
+mod columnar;
+mod compare;
+mod concurrent;
+mod differential;
+mod duration_format;
+mod environment;
+mod exec;
+mod fingerprint;
+mod incremental;
+mod maps;
+mod meminfo;
+mod model;
+mod ndjson;
+mod openmetrics;
 mod pagemap;
+mod populate;
+mod stats;
+mod swap;
+mod sweep;
+mod thp;
+mod tidy;
+mod tsc;
+mod uffd;
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(
+    author,
+    version,
+    long_version = concat!(env!("CARGO_PKG_VERSION"), " (", env!("GIT_HASH"), ")"),
+    about,
+    long_about = None
+)]
 struct Args {
     /// Total size of the memory mapping (e.g., 1G, 512M, 1024K)
     #[arg(short = 's', long, default_value = "1G")]
@@ -28,97 +71,1073 @@ struct Args {
     #[arg(short = 'p', long, default_value_t = 1)]
     processes: usize,
 
-    /// Suppress normal output in favor of JSON
-    #[arg(long, action)]
+    /// Suppress normal output in favor of JSON (same as `--format json`)
+    #[arg(long, action, conflicts_with = "format")]
     json: bool,
 
+    /// Output format
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+    format: OutputFormat,
+
+    /// With `--format ndjson`, how many results each worker buffers before
+    /// writing them out. Results are never written inside a measured region,
+    /// but writes between iterations can still disturb other workers'
+    /// measurements; a larger interval writes less often, at the cost of
+    /// results arriving later and in bursts. Defaults to 1
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    flush_interval: Option<u64>,
+
+    /// How JSON output writes each result's duration
+    #[arg(long, value_enum, default_value_t = duration_format::DurationFormat::Struct)]
+    duration_format: duration_format::DurationFormat,
+
+    /// Trace ID attached to OpenMetrics exemplars; generated if not given
+    #[arg(long)]
+    run_id: Option<String>,
+
     /// Iterations to run
-    #[arg(short = 'i', long, default_value = "1")]
+    #[arg(
+        short = 'i',
+        long,
+        default_value = "1",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
     iterations: u64,
+
+    /// Extra passes dirtying the region before each measured operation, to
+    /// separate first-dirty cost from steady-state dirty cost
+    #[arg(long, default_value_t = 0)]
+    dirty_passes: usize,
+
+    /// Order in which dirtied pages are first touched
+    #[arg(long, value_enum, default_value_t = TouchOrder::Ascending)]
+    touch_order: TouchOrder,
+
+    /// Protection of the memory mapping
+    #[arg(long, value_enum, default_value_t = Protection::Rw)]
+    prot: Protection,
+
+    /// Dirty pages in runs of this many pages spread evenly over the region,
+    /// with gaps sized to keep the dirty fraction, instead of one contiguous
+    /// prefix
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    dirty_run_length: Option<u64>,
+
+    /// Measure PAGEMAP_SCAN at dirty run lengths from 1 to 256 pages, holding
+    /// the dirty fraction fixed, instead of running the clearing strategies
+    #[arg(long, action, conflicts_with = "dirty_run_length")]
+    sweep_run_length: bool,
+
+    /// Measure scan plus clear overhead per millisecond tracked at scan
+    /// intervals from 1 to 100ms, while a background thread dirties random
+    /// pages at `--dirty-rate`, instead of running the clearing strategies.
+    /// `--iterations` sets the scans per interval
+    #[arg(long, action, conflicts_with = "sweep_run_length")]
+    sweep_scan_interval: bool,
+
+    /// Measure the regions PAGEMAP_SCAN reports as clean one-page gaps in an
+    /// otherwise dirty region get denser, from one in 1024 pages to every other
+    /// page, instead of running the clearing strategies
+    #[arg(long, action, conflicts_with_all = ["dirty_run_length", "sweep_run_length", "sweep_scan_interval"])]
+    sweep_gaps: bool,
+
+    /// Pages per millisecond dirtied by the background writer
+    #[arg(long, default_value_t = 64.0, requires = "sweep_scan_interval")]
+    dirty_rate: f64,
+
+    /// Make every Nth page of the region a PROT_NONE guard page, fragmenting it
+    /// into many small VMAs like a hardened allocator
+    #[arg(long, value_parser = clap::value_parser!(u64).range(2..))]
+    guard_pages: Option<u64>,
+
+    /// Back the memory mapping with hugetlb pages (MAP_HUGETLB)
+    #[arg(long, action)]
+    huge_pages: bool,
+
+    /// Fall back to regular pages if hugetlb pages can't be obtained
+    #[arg(long, action, requires = "huge_pages")]
+    huge_pages_fallback: bool,
+
+    /// Leak each benchmark region instead of unmapping it, so the process exits
+    /// without paying munmap. For measurement only: the regions of every
+    /// strategy stay mapped until exit, and the kernel reclaims them then
+    #[arg(long, action)]
+    leak_regions: bool,
+
+    /// Map a fresh region for every iteration and unmap it afterwards, like
+    /// short-lived allocations, timing the whole cycle
+    #[arg(long, action, conflicts_with = "leak_regions")]
+    churn: bool,
+
+    /// Run the strategies both cold, on a freshly mapped region each
+    /// iteration, and warm, on one reused region, and report the difference
+    /// in their medians as the cost of faulting the region in
+    #[arg(long, action, conflicts_with_all = ["churn", "leak_regions"])]
+    cold_warm: bool,
+
+    /// Instead of benchmarking, dirty the region once, scan it, and write the
+    /// dirty regions (start, end, length, categories) as JSON to this path
+    #[arg(long)]
+    export_regions: Option<std::path::PathBuf>,
+
+    /// Measure MADV_DONTNEED on transparent huge pages with huge page aligned
+    /// versus misaligned ranges, instead of the clearing strategies. The size
+    /// must cover at least two huge pages
+    #[arg(long, action)]
+    thp_madvise_alignment: bool,
+
+    /// Fit a model of scan time as a fixed cost plus costs per region and per
+    /// dirty page, from scans across dirty fractions and run lengths, instead
+    /// of running the clearing strategies
+    #[arg(long, action, conflicts_with = "dirty_run_length")]
+    fit_scan_model: bool,
+
+    /// Clear two identically dirtied regions, one with a full memset and one
+    /// by zeroing only the pages a scan finds, and fail unless their contents
+    /// hash the same, instead of running the clearing strategies
+    #[arg(long, action)]
+    differential: bool,
+
+    /// Dirty a recorded random set of pages, scan, and report the scan's
+    /// false positives, false negatives, precision and recall, instead of the
+    /// clearing strategies
+    #[arg(long, action)]
+    accuracy: bool,
+
+    /// Measure incremental dirty tracking across two write phases instead of
+    /// the clearing strategies
+    #[arg(long, action)]
+    incremental: bool,
+
+    /// Measure write-protect scans while another thread keeps dirtying the
+    /// region, against a quiescent baseline, instead of the clearing strategies
+    #[arg(long, action, conflicts_with = "incremental")]
+    concurrent_dirty: bool,
+
+    /// Swap the dirty pages out with MADV_PAGEOUT, then measure scanning for
+    /// and clearing them, instead of the clearing strategies. Requires swap
+    #[arg(long, action, conflicts_with_all = ["incremental", "concurrent_dirty"])]
+    swap_out: bool,
+
+    /// Fraction of the phase 1 dirty pages rewritten in phase 2
+    #[arg(long, default_value_t = 0.5, requires = "incremental")]
+    phase2_fraction: f64,
+
+    /// Page categories a scan requires, e.g. `written,!file,!pfnzero`; a `!`
+    /// prefix requires the category to be absent
+    #[arg(long)]
+    scan_categories: Option<String>,
+
+    /// Page categories of which a scan requires at least one, e.g. `huge,swapped`;
+    /// defaults to `present,swapped`
+    #[arg(long)]
+    scan_anyof: Option<String>,
+
+    /// Fail a scan that needs more than this many ioctl calls after the first
+    #[arg(long)]
+    max_scan_resumes: Option<usize>,
+
+    /// Capacity of the scan's region buffer; defaults to one region per page.
+    /// A smaller buffer makes scans resume more often
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    scan_buffer_regions: Option<u64>,
+
+    /// How the PagemapScan strategy clears the dirty regions it finds
+    #[arg(long, value_enum, default_value_t = ClearMethod::Memset)]
+    clear_method: ClearMethod,
+
+    /// Have the PagemapScan strategy clear only the N largest dirty regions it
+    /// finds, leaving the rest dirty
+    #[arg(long, conflicts_with = "clear_min_region_size")]
+    clear_top_n_regions: Option<usize>,
+
+    /// Have the PagemapScan strategy clear only the dirty regions of at least
+    /// this size, e.g. `64K`, leaving smaller ones dirty
+    #[arg(long)]
+    clear_min_region_size: Option<String>,
+
+    /// Fail unless every scan finds this many dirty pages, give or take one for
+    /// page rounding
+    #[arg(long)]
+    assert_dirty_count: Option<usize>,
+
+    /// Detect transparent huge pages split by each clearing operation, from
+    /// AnonHugePages in /proc/self/smaps_rollup before and after it
+    #[arg(long, action)]
+    track_thp: bool,
+
+    /// After Madvise and DontneedRemap clear, also time rewriting the dirtied
+    /// pages, which faults them back in, as the refault phase. The pages are
+    /// dropped again afterwards, so each iteration does the extra work outside
+    /// its measured duration
+    #[arg(long, action)]
+    refault: bool,
+
+    /// Also count TSC ticks over each strategy's measured spans. These tick at
+    /// a fixed nominal rate, so they track wall time rather than core cycles
+    /// and don't adjust for frequency scaling. Requires an invariant TSC
+    #[arg(long, action)]
+    cycles: bool,
+
+    /// Check scan results against invariants of the dirtied region, and with a
+    /// single worker thread that MADV_DONTNEED dropped RSS by the dirtied size
+    #[arg(long, action)]
+    verify: bool,
+
+    /// Establish the dirty set with MADV_POPULATE_WRITE instead of writing to
+    /// each page. The pages are faulted in writable and zero-filled, so they're
+    /// resident and written as far as the kernel is concerned. Linux 5.14+
+    #[arg(long, action, conflicts_with = "touch_order")]
+    populate_write: bool,
+
+    /// Measure establishing the dirty set by writing to each page against
+    /// MADV_POPULATE_WRITE, on fresh mappings, instead of the clearing
+    /// strategies
+    #[arg(long, action)]
+    bench_populate: bool,
+
+    /// Measure reading and parsing /proc/self/maps (VMA discovery) instead of
+    /// the clearing strategies
+    #[arg(long, action)]
+    bench_maps: bool,
+
+    /// Extra single-page VMAs to create before benchmarking maps parsing
+    #[arg(long, default_value_t = 0, requires = "bench_maps")]
+    extra_vmas: usize,
+
+    /// Instead of benchmarking, run this command, which takes the rest of the
+    /// command line, and scan its writable mappings once it's ready.
+    /// The command gets an eventfd's number in `$PAGEMAP_SCAN_READY_FD`, must
+    /// write a nonzero count to it once its memory is dirtied, and must then
+    /// keep running until it's killed
+    #[arg(long, num_args = 1.., allow_hyphen_values = true, value_name = "COMMAND")]
+    exec: Vec<String>,
+
+    /// How long to wait for the `--exec` command to signal readiness, e.g.
+    /// `30s`
+    #[arg(long, default_value = "10s", requires = "exec")]
+    exec_timeout: String,
+
+    /// Bootstrap resamples used to estimate a 95% confidence interval for each
+    /// strategy's median duration
+    #[arg(long)]
+    bootstrap: Option<usize>,
+
+    /// Also print a matrix of pairwise median ratios between strategies
+    #[arg(long, action)]
+    pairwise_matrix: bool,
+
+    /// Restrict the whole process to these CPUs before any work starts, e.g.
+    /// `0-3,8`
+    #[arg(long)]
+    cpuset: Option<String>,
+
+    /// Strategies to run, e.g. `pagemap-scan,madvise`; defaults to all of them
+    #[arg(long, value_enum, value_delimiter = ',', conflicts_with = "preset")]
+    strategy: Vec<Strategy>,
+
+    /// Run a curated group of strategies instead of listing them with
+    /// `--strategy`
+    #[arg(long, value_enum)]
+    preset: Option<Preset>,
+
+    /// Seed for all randomized behavior, so runs are reproducible
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// How long to leave the dirtied pages before clearing them, e.g. `500ms`
+    /// or `10s`, so the kernel can age them on its LRU lists. Not measured
+    #[arg(long)]
+    age: Option<String>,
+
+    /// Give each worker thread its own seed, `--seed` plus the thread's index,
+    /// so workers dirty different random patterns
+    #[arg(long, action)]
+    seed_per_thread: bool,
+
+    /// Print a hash of everything but the timings (config, environment, dirty
+    /// pattern, region counts) to check that two runs are comparable
+    #[arg(long, action)]
+    structural_hash: bool,
+
+    /// Instead of benchmarking, print each strategy's median per kernel across
+    /// these saved `--json` outputs
+    #[arg(long, num_args = 1..)]
+    compare_kernels: Vec<std::path::PathBuf>,
+
+    /// Also write the raw results to a Parquet file at this path
+    #[cfg(feature = "parquet")]
+    #[arg(long)]
+    parquet: Option<std::path::PathBuf>,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(ValueEnum, Debug, Copy, Clone, PartialEq, Eq)]
+enum OutputFormat {
+    /// Human-readable summary tables
+    Table,
+    /// The raw results as JSON
+    Json,
+    /// Long-format CSV with one row per (strategy, thread, iteration, phase)
+    Tidy,
+    /// OpenMetrics duration histograms with per-run exemplars
+    #[value(name = "openmetrics")]
+    OpenMetrics,
+    /// Each result as a line of JSON, written while the strategies run
+    Ndjson,
+}
+
+#[derive(ValueEnum, Serialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Protection {
+    /// PROT_READ | PROT_WRITE
+    Rw,
+    /// PROT_READ
+    Ro,
+    /// PROT_READ | PROT_WRITE | PROT_EXEC
+    Rwx,
+}
+
+impl Protection {
+    fn flags(self) -> ProtFlags {
+        match self {
+            Protection::Rw => ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+            Protection::Ro => ProtFlags::PROT_READ,
+            Protection::Rwx => ProtFlags::PROT_READ | ProtFlags::PROT_WRITE | ProtFlags::PROT_EXEC,
+        }
+    }
+}
+
+#[derive(ValueEnum, Serialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum ClearMethod {
+    /// Zero each dirty region with its own memset
+    Memset,
+    /// Read /dev/zero into all the dirty regions of a scan batch with vectored
+    /// preadv calls
+    Preadv,
+}
+
+/// Which of the dirty regions it finds the PagemapScan strategy clears.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ClearSelection {
+    All,
+    /// The N largest regions.
+    TopN(usize),
+    /// Regions of at least this many bytes.
+    MinSize(usize),
+}
+
+#[derive(ValueEnum, Serialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum TouchOrder {
+    /// Lowest address first, which is friendly to prefetchers
+    Ascending,
+    /// Highest address first
+    Descending,
+    /// A seeded shuffle of the dirtied pages
+    Random,
+}
+
+#[derive(ValueEnum, Serialize, Debug, Copy, Clone, PartialEq, Eq)]
 enum Strategy {
+    /// memset the whole region
     MemZero,
+    /// MADV_DONTNEED the whole region
     Madvise,
+    /// Find the dirty regions with PAGEMAP_SCAN and clear only those
     PagemapScan,
+    /// MADV_DONTNEED the whole region, then map fresh memory over it
+    DontneedRemap,
+    /// Find the dirty regions with PAGEMAP_SCAN and clear them on another
+    /// thread
+    BackgroundClear,
+}
+
+impl Strategy {
+    /// Whether the strategy scans for the dirty pages, which is where
+    /// `--assert-dirty-count` is checked.
+    fn scans(self) -> bool {
+        Preset::DirtyTracking.strategies().contains(&self)
+    }
+}
+
+#[derive(ValueEnum, Debug, Copy, Clone, PartialEq, Eq)]
+enum Preset {
+    /// The strategies that find the dirty pages and clear only those:
+    /// pagemap-scan and background-clear
+    DirtyTracking,
+    /// The strategies that clear the whole region: mem-zero, madvise and
+    /// dontneed-remap
+    FullClear,
+    /// Every strategy
+    All,
 }
 
-#[derive(Debug)]
+impl Preset {
+    fn strategies(self) -> &'static [Strategy] {
+        match self {
+            Preset::DirtyTracking => &[Strategy::PagemapScan, Strategy::BackgroundClear],
+            Preset::FullClear => &[
+                Strategy::MemZero,
+                Strategy::Madvise,
+                Strategy::DontneedRemap,
+            ],
+            Preset::All => Strategy::value_variants(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 struct BenchArgs {
     total_size: usize,
     dirty_fraction: f64,
     threads: usize,
     processes: usize,
+    prot: Protection,
+    touch_order: TouchOrder,
+    clear_method: ClearMethod,
+    clear_selection: ClearSelection,
+    dirty_passes: usize,
+    age: Duration,
+    seed: u64,
+    seed_per_thread: bool,
+
+    /// The worker thread running with these arguments; see
+    /// [`BenchArgs::for_thread`].
+    thread: usize,
+
+    /// Write each result to stdout as soon as it's measured, with
+    /// `--format ndjson`.
+    stream: Option<ndjson::Stream>,
+    dirty_run: Option<usize>,
+    guard_interval: Option<usize>,
+    huge_pages: bool,
+    huge_pages_fallback: bool,
+    populate_write: bool,
+    verify: bool,
+    assert_dirty_count: Option<usize>,
+    track_thp: bool,
+    refault: bool,
+    cycles: bool,
+    churn: bool,
+
+    /// Measure every strategy on regions nothing has touched yet, including
+    /// those that normally start from a resident region, for `--cold-warm`.
+    cold: bool,
+    leak_regions: bool,
+    scan_categories: pagemap::ScanCategories,
+    max_scan_resumes: Option<usize>,
+    scan_buffer_regions: Option<usize>,
+}
+
+impl BenchArgs {
+    /// The arguments worker `thread` runs with, which differ only in the
+    /// thread index and, with `--seed-per-thread`, the seed.
+    fn for_thread(&self, thread: usize) -> BenchArgs {
+        let seed = if self.seed_per_thread {
+            self.seed.wrapping_add(thread as u64)
+        } else {
+            self.seed
+        };
+        BenchArgs {
+            seed,
+            thread,
+            ..self.clone()
+        }
+    }
+
+    /// An uninitialized buffer for the regions one scan call returns.
+    fn scan_buffer(&self) -> Box<[MaybeUninit<pagemap::PageRegion>]> {
+        let len = self
+            .scan_buffer_regions
+            .unwrap_or_else(|| self.total_size.div_ceil(rustix::param::page_size()));
+        Box::new_uninit_slice(len)
+    }
+}
+
+/// Time spent in each phase of one measured operation. A phase a strategy
+/// doesn't have, or doesn't separate from another, is `None`.
+#[derive(Serialize, Debug, Default)]
+struct Phases {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub map: Option<Duration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dirty: Option<Duration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scan: Option<Duration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clear: Option<Duration>,
+
+    /// With `--refault`, rewriting the dirtied pages after a strategy dropped
+    /// them, which faults them back in. Not part of the measured duration.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refault: Option<Duration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unmap: Option<Duration>,
 }
 
 #[derive(Serialize, Debug)]
 struct BenchResult {
     pub strategy: Strategy,
+    pub thread: usize,
+    pub iteration: usize,
+
+    /// Full version of the kernel the result was measured on.
+    pub kernel: String,
     pub total_size: usize,
     pub dirty_fraction: f64,
+    #[serde(serialize_with = "duration_format::serialize")]
     pub duration: Duration,
+    pub phases: Phases,
     pub threads: usize,
     pub processes: usize,
+    pub prot: Protection,
+    pub touch_order: TouchOrder,
+    pub dirty_passes: usize,
+
+    /// The seed this result's worker ran with.
+    pub seed: u64,
+
+    /// With `--age`, how long the dirtied pages were left before clearing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub age: Option<Duration>,
+
+    /// Length in pages of each run of dirtied pages, if not one prefix.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dirty_run: Option<usize>,
+
+    /// Every this many pages, one is a PROT_NONE guard page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub guard_interval: Option<usize>,
+
+    /// Whether the region was actually backed by hugetlb pages.
+    pub huge_pages: bool,
+
+    /// For strategies that defer clearing to another thread, the time until
+    /// that clearing completed. `duration` is then just the foreground latency.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background_duration: Option<Duration>,
+
+    /// With `--churn`, the whole map, dirty, clear and unmap cycle.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cycle_duration: Option<Duration>,
+
+    /// Number of dirty regions the scan reported, for scan-based strategies.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scan_regions: Option<usize>,
+
+    /// How scan-found regions were cleared, for the PagemapScan strategy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clear_method: Option<ClearMethod>,
+
+    /// With `--clear-top-n-regions` or `--clear-min-region-size`, the dirty
+    /// bytes the scan found that were cleared and that were left dirty.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes_cleared: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes_skipped: Option<usize>,
+
+    /// With `--track-thp`, whether clearing split any transparent huge page,
+    /// and an estimate of how many.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thp_split: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thp_pages_split: Option<usize>,
+
+    /// Extra ioctl calls the scan needed after the first to cover the region.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scan_resumes: Option<usize>,
+
+    /// Pages the scan walked, and how many of them it reported dirty.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scan_pages_walked: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scan_pages_matched: Option<usize>,
+
+    /// Scan throughput over the scan phase, in the units its cost scales with.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scan_pages_per_sec: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scan_regions_per_sec: Option<f64>,
+
+    /// With `--cycles`, TSC ticks over the same spans as `duration`. These are
+    /// at the TSC's nominal rate, not core cycles.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cycles: Option<u64>,
 }
 
 impl BenchResult {
-    fn new(args: &BenchArgs, strategy: Strategy, duration: Duration) -> Self {
+    fn new(
+        args: &BenchArgs,
+        region: &MemoryRegion,
+        strategy: Strategy,
+        duration: Duration,
+    ) -> Self {
         let BenchArgs {
             total_size,
             dirty_fraction,
             threads,
             processes,
+            prot,
+            touch_order,
+            dirty_passes,
+            seed,
+            thread,
+            dirty_run,
+            guard_interval,
             ..
         } = *args;
         BenchResult {
             strategy,
+            thread,
+            // Filled in by `run_iterations`.
+            iteration: 0,
+            kernel: environment::kernel_version().to_string(),
             total_size,
             dirty_fraction,
             duration,
+            phases: Phases::default(),
             threads,
             processes,
+            prot,
+            touch_order,
+            dirty_passes,
+            seed,
+            age: (!args.age.is_zero()).then_some(args.age),
+            dirty_run,
+            guard_interval,
+            huge_pages: region.huge_pages,
+            background_duration: None,
+            cycle_duration: None,
+            scan_regions: None,
+            scan_resumes: None,
+            scan_pages_walked: None,
+            scan_pages_matched: None,
+            scan_pages_per_sec: None,
+            scan_regions_per_sec: None,
+            clear_method: None,
+            bytes_cleared: None,
+            bytes_skipped: None,
+            thp_split: None,
+            thp_pages_split: None,
+            cycles: None,
+        }
+    }
+
+    /// The bytes the clear phase cleared: what the scan selected for clearing
+    /// for scan-based strategies, the whole region for the others.
+    fn cleared_bytes(&self) -> usize {
+        self.bytes_cleared
+            .or_else(|| {
+                self.scan_pages_matched
+                    .map(|pages| pages * rustix::param::page_size())
+            })
+            .unwrap_or(self.total_size)
+    }
+
+    /// Record the pages a scan walked and matched, and its rates over the scan
+    /// phase and `scan_regions`, which must already be set. Scans always walk
+    /// the whole region, resuming until they reach its end.
+    fn record_scan_pages(&mut self, reported_bytes: usize) {
+        let page_size = rustix::param::page_size();
+        let walked = self.total_size.div_ceil(page_size);
+        self.scan_pages_walked = Some(walked);
+        self.scan_pages_matched = Some(reported_bytes / page_size);
+        if let Some(scan) = self.phases.scan.filter(|d| !d.is_zero()) {
+            let secs = scan.as_secs_f64();
+            self.scan_pages_per_sec = Some(walked as f64 / secs);
+            self.scan_regions_per_sec = self.scan_regions.map(|n| n as f64 / secs);
         }
     }
 }
 
+/// A strategy run that failed, reported alongside the successful results.
+#[derive(Serialize, Debug)]
+struct BenchError {
+    pub strategy: Strategy,
+    pub thread: usize,
+    pub error: String,
+}
+
+#[derive(Serialize, Debug)]
+struct BenchOutput<'a> {
+    pub environment: &'a environment::Environment,
+    pub results: &'a [BenchResult],
+    pub errors: &'a [BenchError],
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub structural_hash: Option<String>,
+
+    /// The best strategy against the runner-up, by median.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verdict: Option<stats::Verdict>,
+}
+
 struct MemoryRegion<'a> {
     ptr: *mut u8,
     size: usize,
     dirty_pct: f64,
+    prot: ProtFlags,
+    flags: MapFlags,
+    huge_pages: bool,
+
+    /// Page indices in the order they're touched when dirtying, or `None` to
+    /// dirty in ascending order with a single fill.
+    touch_order: Option<Vec<usize>>,
+
+    /// Dirtied pages form runs of `run` pages, one starting every `period`
+    /// pages, or `None` to dirty a contiguous prefix.
+    dirty_runs: Option<DirtyRuns>,
+
+    /// Every this many pages, the last one is a PROT_NONE guard page.
+    guard_interval: Option<usize>,
+
+    /// Dirty with MADV_POPULATE_WRITE rather than by writing.
+    populate_write: bool,
     phantom: PhantomData<&'a [u8]>,
 }
 
+#[derive(Debug, Copy, Clone)]
+struct DirtyRuns {
+    run: usize,
+    period: usize,
+}
+
+impl DirtyRuns {
+    fn new(run: usize, dirty_fraction: f64) -> Self {
+        // Rounding the period down leaves at least enough pattern pages to
+        // reach the dirty fraction; the dirty page count caps the rest.
+        let period = if dirty_fraction > 0.0 {
+            ((run as f64 / dirty_fraction) as usize).max(run)
+        } else {
+            run
+        };
+        DirtyRuns { run, period }
+    }
+
+    /// Position of `page` among the pattern's pages in ascending order, or
+    /// `None` if it lies in a gap.
+    fn rank(self, page: usize) -> Option<usize> {
+        let offset = page % self.period;
+        (offset < self.run).then(|| page / self.period * self.run + offset)
+    }
+}
+
+/// Bytes of a `size` byte region that aren't guard pages, counting a partial
+/// final page in full.
+fn accessible_bytes(size: usize, guard_interval: Option<usize>) -> usize {
+    let page_size = rustix::param::page_size();
+    let pages = size.div_ceil(page_size);
+    let guards = guard_interval.map_or(0, |interval| pages / interval);
+    (pages - guards) * page_size
+}
+
+/// Fail if `needed` bytes of what `what` faults in won't fit in MemAvailable.
+fn check_available(what: &str, needed: usize) -> anyhow::Result<()> {
+    match meminfo::meminfo_bytes("MemAvailable") {
+        Some(available) if needed > available => Err(anyhow::anyhow!(
+            "{} needs {} bytes resident but only {} bytes are available",
+            what,
+            needed,
+            available
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Refuse a run whose worker threads would together fault in more than is
+/// available. Each worker keeps one region resident at a time, and they fill
+/// them concurrently, so the per-region check alone passes while the run as a
+/// whole pushes the machine into reclaim. Hugetlb regions are left to the
+/// per-region check, since whether they get hugetlb pages is only known once
+/// they're mapped.
+fn check_run_fits_resident(args: &BenchArgs, strategies: &[Strategy]) -> anyhow::Result<()> {
+    if args.cold || args.huge_pages || !strategies.contains(&Strategy::MemZero) {
+        return Ok(());
+    }
+    check_available(
+        &format!("Running {} worker thread(s)", args.threads),
+        accessible_bytes(args.total_size, args.guard_interval) * args.threads,
+    )
+}
+
+/// Attempts made to obtain hugetlb pages before giving up (or falling back).
+const HUGETLB_ATTEMPTS: u32 = 5;
+
+/// Delay before the first hugetlb retry; doubled after each failed attempt.
+const HUGETLB_BACKOFF: Duration = Duration::from_millis(10);
+
 impl<'a> MemoryRegion<'a> {
-    pub fn new(size: usize, dirty_pct: f64, force_resident: bool) -> anyhow::Result<Self> {
-        let prot = ProtFlags::PROT_READ | ProtFlags::PROT_WRITE;
-        let flags = MapFlags::MAP_PRIVATE | MapFlags::MAP_ANONYMOUS;
-        let map = unsafe { mmap_anonymous(None, size.try_into()?, prot, flags) }?;
+    pub fn new(args: &BenchArgs, force_resident: bool) -> anyhow::Result<Self> {
+        let size = args.total_size;
+        let prot = args.prot.flags();
+        if force_resident && !prot.contains(ProtFlags::PROT_WRITE) {
+            return Err(anyhow::anyhow!(
+                "Can't force a read-only mapping resident by writing to it"
+            ));
+        }
+
+        let mut flags = MapFlags::MAP_PRIVATE | MapFlags::MAP_ANONYMOUS;
+        let mut huge_pages = false;
+
+        let map = if args.huge_pages {
+            match Self::mmap_hugetlb(size, prot, flags) {
+                Ok(map) => {
+                    flags |= MapFlags::MAP_HUGETLB;
+                    huge_pages = true;
+                    map
+                }
+                Err(_) if args.huge_pages_fallback => {
+                    unsafe { mmap_anonymous(None, size.try_into()?, prot, flags) }?
+                }
+                Err(e) => return Err(e),
+            }
+        } else {
+            unsafe { mmap_anonymous(None, size.try_into()?, prot, flags) }?
+        };
         let map = map.as_ptr() as *mut u8;
 
-        if force_resident {
-            let keep_res_slice = unsafe { slice::from_raw_parts_mut(map, size) };
-            keep_res_slice.fill(0);
-        }
+        // Computed up front so the measured dirtying doesn't pay for it.
+        let pages = size.div_ceil(rustix::param::page_size());
+        let touch_order = match args.touch_order {
+            TouchOrder::Ascending => None,
+            TouchOrder::Descending => Some((0..pages).rev().collect()),
+            TouchOrder::Random => {
+                let mut order: Vec<usize> = (0..pages).collect();
+                order.shuffle(&mut StdRng::seed_from_u64(args.seed));
+                Some(order)
+            }
+        };
 
-        Ok(MemoryRegion {
+        let mut region = MemoryRegion {
             ptr: map,
             size,
-            dirty_pct,
+            dirty_pct: args.dirty_fraction,
+            prot,
+            flags,
+            huge_pages,
+            touch_order,
+            dirty_runs: args
+                .dirty_run
+                .map(|run| DirtyRuns::new(run, args.dirty_fraction)),
+            guard_interval: args.guard_interval,
+            populate_write: args.populate_write,
             phantom: PhantomData,
-        })
+        };
+        region.protect_guard_pages()?;
+
+        if force_resident {
+            // Hugetlb pages come from a reserved pool that MemAvailable
+            // doesn't count, and mmap would already have failed without them.
+            if !huge_pages {
+                region.check_fits_resident()?;
+            }
+            region.fill(size, 0)?;
+        }
+
+        Ok(region)
+    }
+
+    /// Unmap the region, or with `--leak-regions` leave that to process exit.
+    pub fn release(self, args: &BenchArgs) {
+        if args.leak_regions {
+            mem::forget(self);
+        }
+    }
+
+    /// Refuse to fault in more memory than is available, which would otherwise
+    /// get the process OOM-killed partway through the fill.
+    fn check_fits_resident(&self) -> anyhow::Result<()> {
+        check_available("Region", self.accessible_bytes())
+    }
+
+    fn protect_guard_pages(&mut self) -> anyhow::Result<()> {
+        let Some(interval) = self.guard_interval else {
+            return Ok(());
+        };
+        let page_size = rustix::param::page_size();
+        let pages = self.size.div_ceil(page_size);
+        for page in (interval - 1..pages).step_by(interval) {
+            let addr = NonNull::new(self.ptr.wrapping_add(page * page_size))
+                .ok_or_else(|| anyhow::anyhow!("null guard page address"))?;
+            unsafe { mprotect(addr.cast(), page_size, ProtFlags::PROT_NONE) }?;
+        }
+        Ok(())
+    }
+
+    fn is_guard_page(&self, page: usize) -> bool {
+        self.guard_interval
+            .is_some_and(|interval| page % interval == interval - 1)
+    }
+
+    /// Bytes in pages that aren't guard pages, counting a partial final page in full.
+    pub fn accessible_bytes(&self) -> usize {
+        accessible_bytes(self.size, self.guard_interval)
+    }
+
+    /// Fill the first `len` bytes with `value`, skipping guard pages.
+    pub fn fill(&mut self, len: usize, value: u8) -> anyhow::Result<()> {
+        self.ensure_writable()?;
+        let slice = unsafe { slice::from_raw_parts_mut(self.ptr, len) };
+        match self.guard_interval {
+            None => slice.fill(value),
+            Some(interval) => {
+                // Runs of `interval - 1` accessible pages, each followed by a guard page.
+                let page_size = rustix::param::page_size();
+                for run in slice.chunks_mut(interval * page_size) {
+                    let accessible = run.len().min((interval - 1) * page_size);
+                    run[..accessible].fill(value);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Map hugetlb-backed memory, retrying with backoff since the pool can be
+    /// transiently exhausted (e.g. by fragmentation) even when capacity exists.
+    fn mmap_hugetlb(
+        size: usize,
+        prot: ProtFlags,
+        flags: MapFlags,
+    ) -> anyhow::Result<NonNull<libc::c_void>> {
+        let huge_page_size = meminfo::meminfo_bytes("Hugepagesize")
+            .ok_or_else(|| anyhow::anyhow!("hugetlb pages not supported"))?;
+        if !size.is_multiple_of(huge_page_size) {
+            return Err(anyhow::anyhow!(
+                "Size must be a multiple of the huge page size ({} bytes)",
+                huge_page_size
+            ));
+        }
+
+        let flags = flags | MapFlags::MAP_HUGETLB;
+        let mut backoff = HUGETLB_BACKOFF;
+        let mut attempt = 1;
+        loop {
+            match unsafe { mmap_anonymous(None, size.try_into()?, prot, flags) } {
+                Ok(map) => return Ok(map),
+                Err(e) if attempt >= HUGETLB_ATTEMPTS => {
+                    return Err(anyhow::anyhow!(
+                        "Failed to map hugetlb pages after {} attempts: {}",
+                        attempt,
+                        e
+                    ))
+                }
+                Err(_) => {
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// Writing to a mapping without PROT_WRITE would fault, so refuse up front.
+    fn ensure_writable(&self) -> anyhow::Result<()> {
+        if !self.prot.contains(ProtFlags::PROT_WRITE) {
+            return Err(anyhow::anyhow!("Can't write to a read-only mapping"));
+        }
+        Ok(())
+    }
+
+    /// Fault in `len` bytes at `offset` writable with MADV_POPULATE_WRITE, as
+    /// writing to them would, but without changing their contents.
+    fn populate(&self, offset: usize, len: usize) -> anyhow::Result<()> {
+        let ret = unsafe {
+            libc::madvise(
+                self.ptr.add(offset) as *mut libc::c_void,
+                len,
+                libc::MADV_POPULATE_WRITE,
+            )
+        };
+        if ret != 0 {
+            return Err(anyhow::anyhow!(
+                "MADV_POPULATE_WRITE failed (it needs Linux 5.14+): {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+        Ok(())
     }
 
-    pub fn as_mut_slice(&mut self) -> &'a mut [u8] {
-        unsafe { slice::from_raw_parts_mut(self.ptr, self.size) }
+    /// Map fresh anonymous memory over the region at the same address.
+    pub fn remap(&mut self) -> anyhow::Result<()> {
+        let flags = self.flags | MapFlags::MAP_FIXED;
+        let addr = NonZeroUsize::new(self.ptr as usize);
+        let map = unsafe { mmap_anonymous(addr, self.size.try_into()?, self.prot, flags) }?;
+
+        // MAP_FIXED replaces the old mapping in place, so the region still owns
+        // exactly one mapping of `size` bytes and Drop remains correct.
+        self.ptr = map.as_ptr() as *mut u8;
+        self.protect_guard_pages()
     }
 
-    pub fn make_dirty(&mut self) {
+    /// The unmeasured `--dirty-passes` that precede each measured operation.
+    pub fn predirty(&mut self, args: &BenchArgs) -> anyhow::Result<()> {
+        for _ in 0..args.dirty_passes {
+            self.make_dirty()?;
+        }
+        Ok(())
+    }
+
+    pub fn make_dirty(&mut self) -> anyhow::Result<()> {
         let dirty_bytes = (self.size as f64 * self.dirty_pct).round() as usize;
-        if dirty_bytes > 0 {
-            let dirty_slice = unsafe { slice::from_raw_parts_mut(self.ptr, dirty_bytes) };
-            dirty_slice.fill(0xAA);
+        self.dirty_prefix(dirty_bytes)
+    }
+
+    pub fn dirty_prefix(&mut self, dirty_bytes: usize) -> anyhow::Result<()> {
+        self.ensure_writable()?;
+        if dirty_bytes == 0 {
+            return Ok(());
         }
+        let page_size = rustix::param::page_size();
+        let dirty_pages = dirty_bytes.div_ceil(page_size);
+        let (pages, end) = match self.dirty_runs {
+            // Without runs, the prefix ends at `dirty_bytes` itself.
+            None if self.touch_order.is_none() && !self.populate_write => {
+                return self.fill(dirty_bytes, 0xAA);
+            }
+            None => (dirty_pages, dirty_bytes),
+            Some(_) => (self.size.div_ceil(page_size), self.size),
+        };
+        let is_dirtied = |page: usize| match self.dirty_runs {
+            None => page < dirty_pages,
+            Some(runs) => runs.rank(page).is_some_and(|rank| rank < dirty_pages),
+        };
+
+        if self.populate_write {
+            // One madvise per run of dirtied pages, ending at the first page
+            // that isn't dirtied.
+            let mut run_start = None;
+            for page in 0..=pages {
+                let dirtied = page < pages && is_dirtied(page) && !self.is_guard_page(page);
+                match (dirtied, run_start) {
+                    (true, None) => run_start = Some(page),
+                    (false, Some(first)) => {
+                        let offset = first * page_size;
+                        self.populate(offset, (page * page_size).min(end) - offset)?;
+                        run_start = None;
+                    }
+                    _ => {}
+                }
+            }
+            return Ok(());
+        }
+
+        let slice = unsafe { slice::from_raw_parts_mut(self.ptr, end) };
+        let mut touch = |page: usize| {
+            let start = page * page_size;
+            slice[start..(start + page_size).min(end)].fill(0xAA);
+        };
+        match &self.touch_order {
+            None => (0..pages)
+                .filter(|&page| is_dirtied(page) && !self.is_guard_page(page))
+                .for_each(&mut touch),
+            Some(order) => order
+                .iter()
+                .copied()
+                .filter(|&page| is_dirtied(page) && !self.is_guard_page(page))
+                .for_each(&mut touch),
+        }
+        Ok(())
     }
 }
 
@@ -136,6 +1155,22 @@ macro_rules! qprintln {
     };
 }
 
+/// Parse a duration with a `ms`, `s` or `m` suffix, e.g. `500ms`.
+fn parse_duration(duration_str: &str) -> anyhow::Result<Duration> {
+    let invalid = || anyhow::anyhow!("Invalid duration: {}. Use ms, s or m.", duration_str);
+    let split = duration_str
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let (num_str, unit) = duration_str.split_at(split);
+    let num = num_str.parse::<u64>().map_err(|_| invalid())?;
+    match unit {
+        "ms" => Ok(Duration::from_millis(num)),
+        "s" => Ok(Duration::from_secs(num)),
+        "m" => Ok(Duration::from_secs(num * 60)),
+        _ => Err(invalid()),
+    }
+}
+
 fn parse_size(size_str: &str) -> anyhow::Result<usize> {
     let s = size_str.to_uppercase();
     let (num_str, mult_char) = s.split_at(s.len() - 1);
@@ -156,19 +1191,109 @@ fn parse_size(size_str: &str) -> anyhow::Result<usize> {
     Ok(num * mult)
 }
 
+/// Parse a CPU list such as `0-3,8` into the CPUs it names.
+fn parse_cpuset(list: &str) -> anyhow::Result<Vec<usize>> {
+    let invalid = || anyhow::anyhow!("Invalid CPU list: {}", list);
+    let mut cpus = Vec::new();
+    for item in list.split(',') {
+        let (first, last) = match item.split_once('-') {
+            Some((first, last)) => (first, last),
+            None => (item, item),
+        };
+        let first = first.trim().parse::<usize>().map_err(|_| invalid())?;
+        let last = last.trim().parse::<usize>().map_err(|_| invalid())?;
+        if first > last {
+            return Err(invalid());
+        }
+        cpus.extend(first..=last);
+    }
+    Ok(cpus)
+}
+
+/// Set this process's CPU affinity to `cpus`. Threads and processes started
+/// afterwards inherit it.
+fn restrict_to_cpus(cpus: &[usize]) -> anyhow::Result<()> {
+    let mut set = CpuSet::new();
+    for &cpu in cpus {
+        set.set(cpu)
+            .map_err(|_| anyhow::anyhow!("CPU {} is beyond the largest supported CPU set", cpu))?;
+    }
+    sched_setaffinity(Pid::from_raw(0), &set)
+        .map_err(|e| anyhow::anyhow!("Failed to restrict to CPUs {:?}: {}", cpus, e))?;
+    Ok(())
+}
+
 fn main() -> anyhow::Result<()> {
     let args = Args::parse();
+    if !args.compare_kernels.is_empty() {
+        return compare::compare_kernels(&args.compare_kernels);
+    }
+    if let Some(cpuset) = &args.cpuset {
+        restrict_to_cpus(&parse_cpuset(cpuset)?)?;
+    }
+    duration_format::set(args.duration_format);
     let total_size = parse_size(&args.size)?;
     let dirty_fraction = args.dirty_fraction;
-    let quiet = args.json;
+    let format = if args.json {
+        OutputFormat::Json
+    } else {
+        args.format
+    };
+    let quiet = format != OutputFormat::Table;
 
     let bench_args = BenchArgs {
         total_size,
         dirty_fraction,
         threads: args.threads,
         processes: args.processes,
+        prot: args.prot,
+        touch_order: args.touch_order,
+        clear_method: args.clear_method,
+        clear_selection: match (args.clear_top_n_regions, &args.clear_min_region_size) {
+            (Some(n), _) => ClearSelection::TopN(n),
+            (None, Some(size)) => ClearSelection::MinSize(parse_size(size)?),
+            (None, None) => ClearSelection::All,
+        },
+        dirty_passes: args.dirty_passes,
+        age: args
+            .age
+            .as_deref()
+            .map(parse_duration)
+            .transpose()?
+            .unwrap_or_default(),
+        seed: args.seed,
+        seed_per_thread: args.seed_per_thread,
+        thread: 0,
+        stream: (format == OutputFormat::Ndjson).then_some(ndjson::Stream {
+            flush_interval: args.flush_interval.unwrap_or(1) as usize,
+        }),
+        dirty_run: args.dirty_run_length.map(|n| n as usize),
+        guard_interval: args.guard_pages.map(|n| n as usize),
+        huge_pages: args.huge_pages,
+        huge_pages_fallback: args.huge_pages_fallback,
+        populate_write: args.populate_write,
+        verify: args.verify,
+        assert_dirty_count: args.assert_dirty_count,
+        track_thp: args.track_thp,
+        refault: args.refault,
+        cycles: args.cycles,
+        churn: args.churn,
+        cold: false,
+        leak_regions: args.leak_regions,
+        scan_categories: pagemap::ScanCategories::parse(
+            args.scan_categories.as_deref(),
+            args.scan_anyof.as_deref(),
+        )?,
+        max_scan_resumes: args.max_scan_resumes,
+        scan_buffer_regions: args.scan_buffer_regions.map(|n| n as usize),
     };
 
+    if args.cycles && !tsc::has_invariant_tsc() {
+        return Err(anyhow::anyhow!(
+            "--cycles requires a CPU with an invariant TSC"
+        ));
+    }
+
     if !(0.0..=1.0).contains(&dirty_fraction) {
         return Err(anyhow::anyhow!(
             "Dirty fraction must be between 0.0 and 1.0"
@@ -189,62 +1314,637 @@ fn main() -> anyhow::Result<()> {
     );
     qprintln!(quiet, "------------------------------\n");
 
+    let per_result_format = matches!(
+        format,
+        OutputFormat::Tidy | OutputFormat::OpenMetrics | OutputFormat::Ndjson
+    );
+    let other_mode = args.bench_maps
+        || args.cold_warm
+        || !args.exec.is_empty()
+        || args.bench_populate
+        || args.accuracy
+        || args.differential
+        || args.fit_scan_model
+        || args.thp_madvise_alignment
+        || args.export_regions.is_some()
+        || args.incremental
+        || args.concurrent_dirty
+        || args.swap_out
+        || args.sweep_run_length
+        || args.sweep_scan_interval
+        || args.sweep_gaps;
+    if per_result_format && other_mode {
+        return Err(anyhow::anyhow!(
+            "--format {} is only supported for the clearing strategies",
+            format.to_possible_value().unwrap().get_name()
+        ));
+    }
+
+    let strategies = match args.preset {
+        Some(preset) => preset.strategies().to_vec(),
+        None if args.strategy.is_empty() => Strategy::value_variants().to_vec(),
+        None => args.strategy.clone(),
+    };
+    let checks_dirty_count = if other_mode && !args.cold_warm {
+        args.export_regions.is_some()
+    } else {
+        strategies.iter().any(|s| s.scans())
+    };
+    if args.assert_dirty_count.is_some() && !checks_dirty_count {
+        return Err(anyhow::anyhow!(
+            "--assert-dirty-count needs a strategy that scans (pagemap-scan or \
+             background-clear) or --export-regions"
+        ));
+    }
+    if args.flush_interval.is_some() && format != OutputFormat::Ndjson {
+        return Err(anyhow::anyhow!("--flush-interval requires --format ndjson"));
+    }
+
+    if args.bench_maps {
+        let results = (0..args.threads)
+            .into_par_iter()
+            .map(|_| {
+                maps::run(
+                    args.iterations,
+                    args.extra_vmas,
+                    args.threads,
+                    args.processes,
+                )
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        if format == OutputFormat::Json {
+            println!("{}", serde_json::to_string(&results)?);
+        } else {
+            maps::print_summary(&results);
+        }
+        return Ok(());
+    }
+
+    if args.bench_populate {
+        let results = (0..args.threads)
+            .into_par_iter()
+            .map(|thread| populate::run(&bench_args.for_thread(thread), args.iterations))
+            .collect::<anyhow::Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        if format == OutputFormat::Json {
+            println!("{}", serde_json::to_string(&results)?);
+        } else {
+            populate::print_summary(&results);
+        }
+        return Ok(());
+    }
+
+    if args.sweep_run_length {
+        let points = sweep::run_lengths(&bench_args, args.iterations)?;
+        if format == OutputFormat::Json {
+            println!("{}", serde_json::to_string(&points)?);
+        } else {
+            sweep::print_run_lengths(&points);
+        }
+        return Ok(());
+    }
+
+    if args.fit_scan_model {
+        let model = model::fit(&bench_args, args.iterations)?;
+        if format == OutputFormat::Json {
+            println!("{}", serde_json::to_string(&model)?);
+        } else {
+            model::print_model(&model);
+        }
+        return Ok(());
+    }
+
+    if !args.exec.is_empty() {
+        let timeout = parse_duration(&args.exec_timeout)?;
+        let results = exec::run(&bench_args, &args.exec, timeout, args.iterations)?;
+        if format == OutputFormat::Json {
+            println!("{}", serde_json::to_string(&results)?);
+        } else {
+            exec::print_summary(&results);
+        }
+        return Ok(());
+    }
+
+    if args.differential {
+        let results = differential::run(&bench_args, args.iterations)?;
+        if format == OutputFormat::Json {
+            println!("{}", serde_json::to_string(&results)?);
+        } else {
+            differential::print_summary(&results);
+        }
+        return Ok(());
+    }
+
+    if args.accuracy {
+        let results = accuracy::run(&bench_args, args.iterations)?;
+        if format == OutputFormat::Json {
+            println!("{}", serde_json::to_string(&results)?);
+        } else {
+            accuracy::print_summary(&results);
+        }
+        return Ok(());
+    }
+
+    if args.sweep_gaps {
+        let sweep = sweep::gaps(&bench_args, args.iterations)?;
+        if format == OutputFormat::Json {
+            println!("{}", serde_json::to_string(&sweep)?);
+        } else {
+            sweep::print_gaps(&sweep);
+        }
+        return Ok(());
+    }
+
+    if args.sweep_scan_interval {
+        if !args.dirty_rate.is_finite() || args.dirty_rate <= 0.0 {
+            return Err(anyhow::anyhow!("Dirty rate must be positive"));
+        }
+        let points = sweep::scan_intervals(&bench_args, args.iterations, args.dirty_rate)?;
+        if format == OutputFormat::Json {
+            println!("{}", serde_json::to_string(&points)?);
+        } else {
+            sweep::print_scan_intervals(&points);
+        }
+        return Ok(());
+    }
+
+    if let Some(path) = &args.export_regions {
+        let exported = export_regions(&bench_args, path)?;
+        qprintln!(
+            quiet,
+            "Exported {} dirty regions to {}",
+            exported,
+            path.display()
+        );
+        return Ok(());
+    }
+
+    if args.thp_madvise_alignment {
+        let results = (0..args.threads)
+            .into_par_iter()
+            .map(|_| thp::run(total_size, args.iterations))
+            .collect::<anyhow::Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        if format == OutputFormat::Json {
+            println!("{}", serde_json::to_string(&results)?);
+        } else {
+            thp::print_summary(&results);
+        }
+        return Ok(());
+    }
+
+    if args.swap_out {
+        let results = (0..args.threads)
+            .into_par_iter()
+            .map(|thread| swap::run(&bench_args.for_thread(thread), args.iterations))
+            .collect::<anyhow::Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        if format == OutputFormat::Json {
+            println!("{}", serde_json::to_string(&results)?);
+        } else {
+            swap::print_summary(&results);
+        }
+        return Ok(());
+    }
+
+    if args.concurrent_dirty {
+        let results = (0..args.threads)
+            .into_par_iter()
+            .map(|thread| concurrent::run(&bench_args.for_thread(thread), args.iterations))
+            .collect::<anyhow::Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        if format == OutputFormat::Json {
+            println!("{}", serde_json::to_string(&results)?);
+        } else {
+            concurrent::print_summary(&results);
+        }
+        return Ok(());
+    }
+
+    if args.incremental {
+        if !(0.0..=1.0).contains(&args.phase2_fraction) {
+            return Err(anyhow::anyhow!(
+                "Phase 2 fraction must be between 0.0 and 1.0"
+            ));
+        }
+        let results = (0..args.threads)
+            .into_par_iter()
+            .map(|thread| {
+                incremental::run(
+                    &bench_args.for_thread(thread),
+                    args.iterations,
+                    args.phase2_fraction,
+                )
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        if format == OutputFormat::Json {
+            println!("{}", serde_json::to_string(&results)?);
+        } else {
+            incremental::print_summary(&results);
+        }
+        return Ok(());
+    }
+
+    let environment = environment::Environment::capture();
+
+    if args.cold_warm {
+        check_run_fits_resident(&bench_args, &strategies)?;
+        let run = |cold| {
+            let run_args = BenchArgs {
+                churn: cold,
+                cold,
+                ..bench_args.clone()
+            };
+            run_strategies(&run_args, &strategies, args.iterations)
+        };
+        let (cold, mut errors) = run(true);
+        let (warm, warm_errors) = run(false);
+        errors.extend(warm_errors);
+        let cold = stats::summarize(&cold, args.bootstrap, args.seed);
+        let warm = stats::summarize(&warm, args.bootstrap, args.seed);
+        let report = cold_warm::report(&cold, &warm);
+        if format == OutputFormat::Json {
+            let output = cold_warm::ColdWarmOutput {
+                environment: &environment,
+                strategies: &report,
+                errors: &errors,
+            };
+            println!("{}", serde_json::to_string(&output)?);
+            return Ok(());
+        }
+        cold_warm::print_report(&report);
+        return report_errors(&errors);
+    }
+
+    check_run_fits_resident(&bench_args, &strategies)?;
+    let (results, errors) = run_strategies(&bench_args, &strategies, args.iterations);
+
+    #[cfg(feature = "parquet")]
+    if let Some(path) = &args.parquet {
+        columnar::write_parquet(path, &results)?;
+    }
+
+    let structural_hash = if args.structural_hash {
+        Some(fingerprint::structural_hash(
+            &bench_args,
+            &environment,
+            &results,
+            &errors,
+        )?)
+    } else {
+        None
+    };
+
+    match format {
+        OutputFormat::Json => {
+            let output = BenchOutput {
+                environment: &environment,
+                results: &results,
+                errors: &errors,
+                structural_hash: structural_hash.map(|hash| format!("{:016x}", hash)),
+                verdict: stats::verdict(&stats::summarize(&results, args.bootstrap, args.seed)),
+            };
+            println!("{}", serde_json::to_string(&output)?);
+            return Ok(());
+        }
+        // Already written as the results came in.
+        OutputFormat::Ndjson => {}
+        OutputFormat::Tidy => tidy::write_tidy(std::io::stdout().lock(), &results)?,
+        OutputFormat::OpenMetrics => {
+            let run_id = args.run_id.clone().unwrap_or_else(generate_run_id);
+            openmetrics::write_openmetrics(std::io::stdout().lock(), &results, &run_id)?
+        }
+        OutputFormat::Table => {
+            let summaries = stats::summarize(&results, args.bootstrap, args.seed);
+            stats::print_summary(&summaries);
+            if let Some(verdict) = stats::verdict(&summaries) {
+                println!();
+                stats::print_verdict(&verdict);
+            }
+            if args.pairwise_matrix {
+                println!();
+                stats::print_pairwise_matrix(&summaries);
+            }
+            if let Some(hash) = structural_hash {
+                println!("\nStructural hash: {:016x}", hash);
+            }
+        }
+    }
+    report_errors(&errors)
+}
+
+/// Run each of `strategies` for `iterations` on every worker thread, keeping
+/// the results of the runs that succeeded alongside the errors of those that
+/// didn't.
+fn run_strategies(
+    bench_args: &BenchArgs,
+    strategies: &[Strategy],
+    iterations: u64,
+) -> (Vec<BenchResult>, Vec<BenchError>) {
     // we want to reduce the number of new regions we create
     // while still creating enough work to be meaningful
-    let do_memset = || -> anyhow::Result<Vec<BenchResult>> {
-        let mut region = MemoryRegion::new(total_size, args.dirty_fraction, true)?;
-        (0..args.iterations)
-            .map(|_i| run_benchmark_memset(&bench_args, &mut region))
-            .collect::<anyhow::Result<Vec<BenchResult>>>()
+    let do_memset = |args: &BenchArgs| {
+        run_iterations(args, iterations, true, |region| {
+            run_benchmark_memset(args, region)
+        })
+    };
+
+    let do_madvise = |args: &BenchArgs| {
+        run_iterations(args, iterations, false, |region| {
+            run_benchmark_madvise(args, region)
+        })
     };
 
-    let do_madvise = || {
-        let mut region = MemoryRegion::new(total_size, args.dirty_fraction, false)?;
-        (0..args.iterations)
-            .map(|_i| run_benchmark_madvise(&bench_args, &mut region))
-            .collect::<anyhow::Result<Vec<BenchResult>>>()
+    let do_pagemap_scan = |args: &BenchArgs| {
+        run_iterations(args, iterations, false, |region| {
+            run_benchmark_pagemap_scan(args, region)
+        })
     };
 
-    let do_pagemap_scan = || {
-        let mut region = MemoryRegion::new(total_size, args.dirty_fraction, false)?;
-        (0..args.iterations)
-            .map(|_i| run_benchmark_pagemap_scan(&bench_args, &mut region))
-            .collect::<anyhow::Result<Vec<BenchResult>>>()
+    let do_dontneed_remap = |args: &BenchArgs| {
+        run_iterations(args, iterations, false, |region| {
+            run_benchmark_dontneed_remap(args, region)
+        })
     };
 
-    let results: Vec<BenchResult> = (0..args.threads)
+    let do_background_clear = |args: &BenchArgs| {
+        let clearer = BackgroundClearer::spawn();
+        // The clearer is done with a region once its last wait returned.
+        run_iterations(args, iterations, false, |region| {
+            run_benchmark_background_clear(args, region, &clearer)
+        })
+    };
+
+    run_on_workers(bench_args.threads, strategies, |thread, strategy| {
+        let thread_args = bench_args.for_thread(thread);
+        match strategy {
+            Strategy::MemZero => do_memset(&thread_args),
+            Strategy::Madvise => do_madvise(&thread_args),
+            Strategy::PagemapScan => do_pagemap_scan(&thread_args),
+            Strategy::DontneedRemap => do_dontneed_remap(&thread_args),
+            Strategy::BackgroundClear => do_background_clear(&thread_args),
+        }
+    })
+}
+
+/// Call `run` for each of `strategies` on each of `threads` worker threads.
+/// A run that panics, such as on a failed `--verify` assertion, is reported as
+/// that run's error like any other failure, once unwinding has released its
+/// regions; with `panic = "abort"` it still takes the whole process down.
+fn run_on_workers(
+    threads: usize,
+    strategies: &[Strategy],
+    run: impl Fn(usize, Strategy) -> anyhow::Result<Vec<BenchResult>> + Sync,
+) -> (Vec<BenchResult>, Vec<BenchError>) {
+    let runs: Vec<(usize, Strategy, anyhow::Result<Vec<BenchResult>>)> = (0..threads)
         .into_par_iter()
-        .map(|_| [do_memset(), do_madvise(), do_pagemap_scan()])
-        .flatten()
-        .flatten()
-        .flatten()
+        .flat_map_iter(|thread| {
+            strategies
+                .iter()
+                .map(|&strategy| {
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| run(thread, strategy)))
+                        .unwrap_or_else(|payload| {
+                            let message = payload
+                                .downcast_ref::<&str>()
+                                .copied()
+                                .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                                .unwrap_or("unknown panic payload");
+                            Err(anyhow::anyhow!("panicked: {}", message))
+                        });
+                    (thread, strategy, result)
+                })
+                .collect::<Vec<_>>()
+        })
         .collect();
 
-    if args.json {
-        println!("{}", serde_json::to_string(&results)?);
+    // A failing strategy shouldn't discard the data from the ones that worked.
+    let mut results: Vec<BenchResult> = Vec::new();
+    let mut errors: Vec<BenchError> = Vec::new();
+    for (thread, strategy, run) in runs {
+        match run {
+            Ok(run) => results.extend(run),
+            Err(e) => errors.push(BenchError {
+                strategy,
+                thread,
+                error: format!("{:#}", e),
+            }),
+        }
     }
+    (results, errors)
+}
 
+/// Print each failed strategy run, failing if there were any.
+fn report_errors(errors: &[BenchError]) -> anyhow::Result<()> {
+    for e in errors {
+        eprintln!("{:?} (thread {}) failed: {}", e.strategy, e.thread, e.error);
+    }
+    if !errors.is_empty() {
+        return Err(anyhow::anyhow!("{} strategy run(s) failed", errors.len()));
+    }
     Ok(())
 }
 
+#[derive(Serialize, Debug)]
+struct RegionExport<'a> {
+    pub base: u64,
+    pub len: usize,
+    pub regions: &'a [pagemap::PageRegion],
+}
+
+/// Dirty a region as configured, scan it and write the dirty regions to
+/// `path`. Returns the number of regions written.
+fn export_regions(args: &BenchArgs, path: &std::path::Path) -> anyhow::Result<usize> {
+    let mut region = MemoryRegion::new(args, false)?;
+    region.make_dirty()?;
+    let mut buffer = args.scan_buffer();
+    let mut regions = Vec::new();
+    pagemap::for_each_dirty_region(
+        region.ptr,
+        args.total_size,
+        buffer.as_mut(),
+        &args.scan_categories,
+        args.max_scan_resumes,
+        |dirty_regions| {
+            regions.extend_from_slice(dirty_regions);
+            Ok(())
+        },
+    )?;
+    let reported = regions.iter().map(|r| r.end - r.start).sum();
+    verify_scan(args, &region, reported)?;
+    let export = RegionExport {
+        base: region.ptr as u64,
+        len: args.total_size,
+        regions: &regions,
+    };
+    serde_json::to_writer(File::create(path)?, &export)?;
+    region.release(args);
+    Ok(regions.len())
+}
+
+/// Run `op` for each iteration on one reused region, or with `--churn` on a
+/// freshly mapped region each time, additionally timing the map and unmap.
+fn run_iterations(
+    args: &BenchArgs,
+    iterations: u64,
+    force_resident: bool,
+    mut op: impl FnMut(&mut MemoryRegion) -> anyhow::Result<BenchResult>,
+) -> anyhow::Result<Vec<BenchResult>> {
+    let force_resident = force_resident && !args.cold;
+    let mut stream = args.stream.map(ndjson::Batch::new);
+    let mut record = |iteration: u64, mut result: BenchResult| {
+        result.iteration = iteration as usize;
+        if let Some(stream) = &mut stream {
+            stream.push(&result)?;
+        }
+        anyhow::Ok(result)
+    };
+
+    let results = if !args.churn {
+        let mut region = MemoryRegion::new(args, force_resident)?;
+        let results = (0..iterations)
+            .map(|i| record(i, op(&mut region)?))
+            .collect::<anyhow::Result<Vec<BenchResult>>>();
+        region.release(args);
+        results
+    } else {
+        (0..iterations)
+            .map(|i| {
+                let start = Instant::now();
+                let mut region = MemoryRegion::new(args, force_resident)?;
+                let map = start.elapsed();
+                let mut result = op(&mut region)?;
+                let start = Instant::now();
+                drop(region);
+                let unmap = start.elapsed();
+
+                result.phases.map = Some(map);
+                result.phases.unmap = Some(unmap);
+                result.cycle_duration = Some(map + result.duration + unmap);
+                record(i, result)
+            })
+            .collect()
+    };
+
+    // Write whatever is left, including the results before a failure.
+    if let Some(stream) = &mut stream {
+        stream.flush()?;
+    }
+    results
+}
+
+/// A run ID unique enough to tell runs apart in an observability stack.
+fn generate_run_id() -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64);
+    format!("{:016x}", nanos ^ (u64::from(std::process::id()) << 32))
+}
+
 fn run_benchmark_memset(
     args: &BenchArgs,
     region: &mut MemoryRegion,
 ) -> anyhow::Result<BenchResult> {
+    region.predirty(args)?;
+    let mut cycles = tsc::Cycles::new(args.cycles);
+    cycles.resume();
     let start = Instant::now();
-    region.make_dirty();
-    region.as_mut_slice().fill(0);
-    let duration = start.elapsed();
+    region.make_dirty()?;
+    let dirtied = Instant::now();
+    cycles.pause();
+    thread::sleep(args.age);
+    let thp = ThpTracker::start(args)?;
+    let resumed = Instant::now();
+    cycles.resume();
+    region.fill(args.total_size, 0)?;
+    let end = Instant::now();
+    cycles.pause();
 
-    Ok(BenchResult::new(args, Strategy::MemZero, duration))
+    let mut result = BenchResult::new(
+        args,
+        region,
+        Strategy::MemZero,
+        (dirtied - start) + (end - resumed),
+    );
+    result.phases.dirty = Some(dirtied - start);
+    result.phases.clear = Some(end - resumed);
+    result.cycles = cycles.total();
+    thp.finish(&mut result)?;
+    Ok(result)
 }
 
 fn run_benchmark_madvise(
     args: &BenchArgs,
     region: &mut MemoryRegion,
 ) -> anyhow::Result<BenchResult> {
+    region.predirty(args)?;
+    let mut cycles = tsc::Cycles::new(args.cycles);
+    cycles.resume();
+    let start = Instant::now();
+    region.make_dirty()?;
+    let dirtied = Instant::now();
+    cycles.pause();
+    thread::sleep(args.age);
+    let thp = ThpTracker::start(args)?;
+    let rss = RssCheck::start(args, region)?;
+    let resumed = Instant::now();
+    cycles.resume();
+    let ret = unsafe {
+        libc::madvise(
+            region.ptr as *mut libc::c_void,
+            args.total_size,
+            libc::MADV_DONTNEED,
+        )
+    };
+    let end = Instant::now();
+    cycles.pause();
+
+    if ret != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    rss.finish()?;
+
+    let mut result = BenchResult::new(
+        args,
+        region,
+        Strategy::Madvise,
+        (dirtied - start) + (end - resumed),
+    );
+    result.phases.dirty = Some(dirtied - start);
+    result.phases.clear = Some(end - resumed);
+    result.cycles = cycles.total();
+    thp.finish(&mut result)?;
+    result.phases.refault = measure_refault(args, region)?;
+    Ok(result)
+}
+
+/// With `--refault`, time rewriting the pages the dirty phase wrote, after a
+/// strategy dropped them, then drop them again so the next iteration starts
+/// from the same state it would have without the measurement.
+fn measure_refault(
+    args: &BenchArgs,
+    region: &mut MemoryRegion,
+) -> anyhow::Result<Option<Duration>> {
+    if !args.refault {
+        return Ok(None);
+    }
     let start = Instant::now();
-    region.make_dirty();
+    region.make_dirty()?;
+    let refault = start.elapsed();
     let ret = unsafe {
         libc::madvise(
             region.ptr as *mut libc::c_void,
@@ -252,33 +1952,455 @@ fn run_benchmark_madvise(
             libc::MADV_DONTNEED,
         )
     };
-    let duration = start.elapsed();
+    if ret != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(Some(refault))
+}
 
+fn run_benchmark_dontneed_remap(
+    args: &BenchArgs,
+    region: &mut MemoryRegion,
+) -> anyhow::Result<BenchResult> {
+    region.predirty(args)?;
+    let mut cycles = tsc::Cycles::new(args.cycles);
+    cycles.resume();
+    let start = Instant::now();
+    region.make_dirty()?;
+    let dirtied = Instant::now();
+    cycles.pause();
+    thread::sleep(args.age);
+    let thp = ThpTracker::start(args)?;
+    let rss = RssCheck::start(args, region)?;
+    let resumed = Instant::now();
+    cycles.resume();
+    let ret = unsafe {
+        libc::madvise(
+            region.ptr as *mut libc::c_void,
+            args.total_size,
+            libc::MADV_DONTNEED,
+        )
+    };
     if ret != 0 {
         return Err(std::io::Error::last_os_error().into());
     }
+    region.remap()?;
+    let end = Instant::now();
+    cycles.pause();
+    rss.finish()?;
+
+    let mut result = BenchResult::new(
+        args,
+        region,
+        Strategy::DontneedRemap,
+        (dirtied - start) + (end - resumed),
+    );
+    result.phases.dirty = Some(dirtied - start);
+    result.phases.clear = Some(end - resumed);
+    result.cycles = cycles.total();
+    thp.finish(&mut result)?;
+    result.phases.refault = measure_refault(args, region)?;
+    Ok(result)
+}
+
+/// With `--track-thp`, samples this process's THP usage once between a
+/// strategy's dirtying and its clearing, and again after. The sampling happens
+/// between the two measured spans, so it isn't counted in the duration.
+struct ThpTracker {
+    before: Option<meminfo::ThpUsage>,
+}
+
+/// Pages of RSS change unrelated to the region, such as stack or allocator
+/// growth, that [`RssCheck`] tolerates.
+const RSS_TOLERANCE_PAGES: usize = 16;
+
+/// Under `--verify`, checks that a strategy's MADV_DONTNEED really released
+/// the dirtied pages, by sampling RSS between dirtying and clearing and again
+/// after. RSS covers the whole process, so the check only runs with a single
+/// worker thread, and not for hugetlb regions, whose pages RSS doesn't count.
+struct RssCheck {
+    before: Option<usize>,
+    expected: usize,
+}
+
+impl RssCheck {
+    fn start(args: &BenchArgs, region: &MemoryRegion) -> anyhow::Result<Self> {
+        let enabled = args.verify && args.threads == 1 && !region.huge_pages;
+        let before = enabled.then(meminfo::resident_bytes).transpose()?;
+
+        // Guard pages inside the dirtied prefix were skipped rather than
+        // dirtied; counting every guard page keeps this a lower bound.
+        let page_size = rustix::param::page_size();
+        let dirty_bytes = (args.total_size as f64 * args.dirty_fraction).round() as usize;
+        let guards = region.size.div_ceil(page_size) - region.accessible_bytes() / page_size;
+        let expected = dirty_bytes.saturating_sub((guards + RSS_TOLERANCE_PAGES) * page_size);
+        Ok(RssCheck { before, expected })
+    }
+
+    fn finish(self) -> anyhow::Result<()> {
+        if let Some(before) = self.before {
+            let dropped = before.saturating_sub(meminfo::resident_bytes()?);
+            if dropped < self.expected {
+                return Err(anyhow::anyhow!(
+                    "verify: RSS dropped by {} bytes after MADV_DONTNEED, expected at least {}; \
+                     the advice was ignored or the pages are locked",
+                    dropped,
+                    self.expected
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ThpTracker {
+    fn start(args: &BenchArgs) -> anyhow::Result<Self> {
+        let before = args.track_thp.then(meminfo::ThpUsage::sample).transpose()?;
+        Ok(ThpTracker { before })
+    }
+
+    fn finish(self, result: &mut BenchResult) -> anyhow::Result<()> {
+        if let Some(before) = self.before {
+            let split = before.pages_split_since(&meminfo::ThpUsage::sample()?);
+            result.thp_split = Some(split > 0);
+            result.thp_pages_split = Some(split);
+        }
+        Ok(())
+    }
+}
+
+/// With `--verify`, check that a scan of a fully dirtied region reported
+/// `reported` bytes covering every page of it, catching region accounting bugs
+/// in the ioctl wrapper or its resume logic. With `--assert-dirty-count`, check
+/// that the scan found the expected number of pages.
+fn verify_scan(args: &BenchArgs, region: &MemoryRegion, reported: u64) -> anyhow::Result<()> {
+    if let Some(expected) = args.assert_dirty_count {
+        // A dirty size that isn't page aligned may round either way.
+        let found = reported as usize / rustix::param::page_size();
+        if found.abs_diff(expected) > 1 {
+            return Err(anyhow::anyhow!(
+                "assert-dirty-count: scan found {} dirty pages, expected {}",
+                found,
+                expected
+            ));
+        }
+    }
 
-    Ok(BenchResult::new(args, Strategy::Madvise, duration))
+    if !args.verify || args.dirty_fraction != 1.0 {
+        return Ok(());
+    }
+    let expected = region.accessible_bytes();
+    if reported != expected as u64 {
+        return Err(anyhow::anyhow!(
+            "verify: scan regions cover {} bytes but {} bytes were dirtied",
+            reported,
+            expected
+        ));
+    }
+    Ok(())
 }
 
 fn run_benchmark_pagemap_scan(
     args: &BenchArgs,
     region: &mut MemoryRegion,
 ) -> anyhow::Result<BenchResult> {
-    let pages = args.total_size / rustix::param::page_size();
+    let dev_zero = match args.clear_method {
+        ClearMethod::Memset => None,
+        ClearMethod::Preadv => Some(File::open("/dev/zero")?),
+    };
+
+    region.predirty(args)?;
+    let mut cycles = tsc::Cycles::new(args.cycles);
+    cycles.resume();
+    let start = Instant::now();
+    region.make_dirty()?;
+    let dirtied = Instant::now();
+    cycles.pause();
+    thread::sleep(args.age);
+    let thp = ThpTracker::start(args)?;
+    let resumed = Instant::now();
+    cycles.resume();
+    let mut regions = args.scan_buffer();
+    let mut reported = 0;
+    let mut scan_regions = 0;
+    // Scanning and clearing interleave, so time the clearing of each batch of
+    // regions and attribute the rest to the scan.
+    let mut clear = Duration::ZERO;
+    let mut cleared = 0;
+    let mut clear_regions = |dirty_regions: &[pagemap::PageRegion]| -> anyhow::Result<()> {
+        let clear_start = Instant::now();
+        match &dev_zero {
+            None => {
+                for dirty_region in dirty_regions {
+                    let start_ptr = dirty_region.start as *mut u8;
+                    let len = usize::try_from(dirty_region.end - dirty_region.start)?;
+                    let region_slice = unsafe { slice::from_raw_parts_mut(start_ptr, len) };
+                    region_slice.fill(0);
+                }
+            }
+            Some(dev_zero) => zero_with_preadv(dev_zero, dirty_regions)?,
+        }
+        clear += clear_start.elapsed();
+        cleared += dirty_regions.iter().map(|r| r.end - r.start).sum::<u64>();
+        Ok(())
+    };
+    // The largest regions can only be picked once the scan has found them all.
+    let mut candidates = Vec::new();
+    let scan_resumes = pagemap::for_each_dirty_region(
+        region.ptr,
+        args.total_size,
+        regions.as_mut(),
+        &args.scan_categories,
+        args.max_scan_resumes,
+        |dirty_regions| {
+            scan_regions += dirty_regions.len();
+            reported += dirty_regions.iter().map(|r| r.end - r.start).sum::<u64>();
+            match args.clear_selection {
+                ClearSelection::All => clear_regions(dirty_regions),
+                ClearSelection::TopN(_) => {
+                    candidates.extend_from_slice(dirty_regions);
+                    Ok(())
+                }
+                ClearSelection::MinSize(min) => {
+                    let selected: Vec<pagemap::PageRegion> = dirty_regions
+                        .iter()
+                        .filter(|r| r.end - r.start >= min as u64)
+                        .copied()
+                        .collect();
+                    clear_regions(&selected)
+                }
+            }
+        },
+    )?;
+    if let ClearSelection::TopN(n) = args.clear_selection {
+        candidates.sort_unstable_by_key(|r| std::cmp::Reverse(r.end - r.start));
+        candidates.truncate(n);
+        clear_regions(&candidates)?;
+    }
+    let end = Instant::now();
+    cycles.pause();
+    verify_scan(args, region, reported)?;
+
+    let mut result = BenchResult::new(
+        args,
+        region,
+        Strategy::PagemapScan,
+        (dirtied - start) + (end - resumed),
+    );
+    result.phases.dirty = Some(dirtied - start);
+    result.phases.scan = Some((end - resumed).saturating_sub(clear));
+    result.phases.clear = Some(clear);
+    result.scan_regions = Some(scan_regions);
+    result.scan_resumes = Some(scan_resumes);
+    result.record_scan_pages(usize::try_from(reported)?);
+    result.clear_method = Some(args.clear_method);
+    if args.clear_selection != ClearSelection::All {
+        result.bytes_cleared = Some(usize::try_from(cleared)?);
+        result.bytes_skipped = Some(usize::try_from(reported - cleared)?);
+    }
+    result.cycles = cycles.total();
+    thp.finish(&mut result)?;
+    Ok(result)
+}
+
+/// Zero `regions` by reading `/dev/zero` into them, up to `IOV_MAX` regions per
+/// preadv call.
+fn zero_with_preadv(dev_zero: &File, regions: &[pagemap::PageRegion]) -> anyhow::Result<()> {
+    let mut iovecs: Vec<libc::iovec> = regions
+        .iter()
+        .map(|r| libc::iovec {
+            iov_base: r.start as *mut libc::c_void,
+            iov_len: (r.end - r.start) as usize,
+        })
+        .collect();
+    let mut next = 0;
+    while next < iovecs.len() {
+        let batch = &iovecs[next..(next + IOV_MAX).min(iovecs.len())];
+        let read =
+            unsafe { libc::preadv(dev_zero.as_raw_fd(), batch.as_ptr(), batch.len() as i32, 0) };
+        if read < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        if read == 0 {
+            return Err(anyhow::anyhow!("preadv of /dev/zero read nothing"));
+        }
+
+        // A single call reads at most about 2GiB, so skip what was filled and
+        // continue partway through a region if need be.
+        let mut read = read as usize;
+        while read > 0 {
+            let iov = &mut iovecs[next];
+            if read >= iov.iov_len {
+                read -= iov.iov_len;
+                next += 1;
+            } else {
+                iov.iov_base = (iov.iov_base as usize + read) as *mut libc::c_void;
+                iov.iov_len -= read;
+                read = 0;
+            }
+        }
+    }
+    Ok(())
+}
 
+/// The most iovecs a single preadv call accepts on Linux.
+const IOV_MAX: usize = 1024;
+
+/// A worker thread that zeroes the dirty ranges handed to it, modelling
+/// allocators that defer clearing off the allocation path.
+struct BackgroundClearer {
+    work: Option<mpsc::Sender<Vec<(usize, usize)>>>,
+    done: mpsc::Receiver<()>,
+    worker: Option<thread::JoinHandle<()>>,
+}
+
+impl BackgroundClearer {
+    fn spawn() -> Self {
+        let (work, work_rx) = mpsc::channel::<Vec<(usize, usize)>>();
+        let (done_tx, done) = mpsc::channel();
+        let worker = thread::spawn(move || {
+            for ranges in work_rx {
+                for (start, len) in ranges {
+                    unsafe { slice::from_raw_parts_mut(start as *mut u8, len) }.fill(0);
+                }
+                if done_tx.send(()).is_err() {
+                    break;
+                }
+            }
+        });
+        BackgroundClearer {
+            work: Some(work),
+            done,
+            worker: Some(worker),
+        }
+    }
+
+    /// Hand `(address, length)` ranges to the worker. The memory must stay
+    /// mapped until the matching [`BackgroundClearer::wait`] returns.
+    fn submit(&self, ranges: Vec<(usize, usize)>) -> anyhow::Result<()> {
+        self.work
+            .as_ref()
+            .and_then(|work| work.send(ranges).ok())
+            .ok_or_else(|| anyhow::anyhow!("background clearing thread exited"))
+    }
+
+    fn wait(&self) -> anyhow::Result<()> {
+        self.done
+            .recv()
+            .map_err(|_| anyhow::anyhow!("background clearing thread exited"))
+    }
+}
+
+impl Drop for BackgroundClearer {
+    fn drop(&mut self) {
+        // Closing the work channel ends the worker's loop.
+        self.work.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn run_benchmark_background_clear(
+    args: &BenchArgs,
+    region: &mut MemoryRegion,
+    clearer: &BackgroundClearer,
+) -> anyhow::Result<BenchResult> {
+    region.predirty(args)?;
+    let mut cycles = tsc::Cycles::new(args.cycles);
+    cycles.resume();
     let start = Instant::now();
-    region.make_dirty();
-    let mut regions: Box<[MaybeUninit<pagemap::PageRegion>]> = Box::new_uninit_slice(pages);
-    let dirty_pages =
-        pagemap::dirty_pages_in_region(region.ptr, args.total_size, regions.as_mut())?;
-    for dirty_region in dirty_pages.regions {
-        let start_ptr = dirty_region.start as *mut u8;
-        let len = usize::try_from(dirty_region.end - dirty_region.start)?;
-        let region_slice = unsafe { slice::from_raw_parts_mut(start_ptr, len) };
-        region_slice.fill(0);
-    }
-    let duration = start.elapsed();
-
-    Ok(BenchResult::new(args, Strategy::PagemapScan, duration))
+    region.make_dirty()?;
+    let dirtied = Instant::now();
+    cycles.pause();
+    thread::sleep(args.age);
+    let thp = ThpTracker::start(args)?;
+    let resumed = Instant::now();
+    cycles.resume();
+    let mut regions = args.scan_buffer();
+    let mut ranges = Vec::new();
+    let scan_resumes = pagemap::for_each_dirty_region(
+        region.ptr,
+        args.total_size,
+        regions.as_mut(),
+        &args.scan_categories,
+        args.max_scan_resumes,
+        |dirty_regions| {
+            for r in dirty_regions {
+                ranges.push((r.start as usize, usize::try_from(r.end - r.start)?));
+            }
+            Ok(())
+        },
+    )?;
+    let reported: usize = ranges.iter().map(|&(_, len)| len).sum();
+    let scan_regions = ranges.len();
+    clearer.submit(ranges)?;
+    let submitted = Instant::now();
+    cycles.pause();
+    clearer.wait()?;
+    let cleared = Instant::now();
+    verify_scan(args, region, reported as u64)?;
+
+    let dirty = dirtied - start;
+    let mut result = BenchResult::new(
+        args,
+        region,
+        Strategy::BackgroundClear,
+        dirty + (submitted - resumed),
+    );
+    result.background_duration = Some(dirty + (cleared - resumed));
+    result.phases.dirty = Some(dirty);
+    result.phases.scan = Some(submitted - resumed);
+    result.phases.clear = Some(cleared - submitted);
+    result.scan_regions = Some(scan_regions);
+    result.scan_resumes = Some(scan_resumes);
+    result.record_scan_pages(reported);
+    result.cycles = cycles.total();
+    thp.finish(&mut result)?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{maps, pagemap, run_on_workers, Strategy};
+
+    fn is_mapped(addr: u64) -> bool {
+        maps::read_maps(None)
+            .unwrap()
+            .iter()
+            .any(|m| m.start <= addr && addr < m.end)
+    }
+
+    #[test]
+    fn worker_panic_becomes_that_runs_error() {
+        let addr = std::sync::atomic::AtomicU64::new(0);
+        let (results, errors) = run_on_workers(
+            2,
+            &[Strategy::MemZero, Strategy::Madvise],
+            |thread, strategy| {
+                if thread == 1 && strategy == Strategy::Madvise {
+                    pagemap::scan_scope(1 << 20, |mapping| {
+                        mapping.as_mut_slice().fill(1);
+                        let mapped = mapping.as_ptr() as u64;
+                        addr.store(mapped, std::sync::atomic::Ordering::Relaxed);
+                        assert!(is_mapped(mapped));
+                        panic!("worker failed mid-benchmark");
+                    })?;
+                }
+                Ok(Vec::new())
+            },
+        );
+
+        assert!(results.is_empty());
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].thread, 1);
+        assert_eq!(errors[0].strategy, Strategy::Madvise);
+        assert_eq!(errors[0].error, "panicked: worker failed mid-benchmark");
+        let addr = addr.into_inner();
+        assert!(
+            !is_mapped(addr),
+            "mapping at {:#x} outlived the panic",
+            addr
+        );
+    }
 }
@@ -0,0 +1,167 @@
+//! Parsing of `/proc/<pid>/maps` for discovering a process's mappings, plus a
+//! benchmark of the discovery itself, whose cost grows with the VMA count.
+
+use crate::stats::percentile;
+use nix::sys::mman::{mmap_anonymous, munmap, MapFlags, ProtFlags};
+use serde::Serialize;
+use std::ffi::c_void;
+use std::fs;
+use std::ptr::NonNull;
+use std::time::{Duration, Instant};
+
+/// One line of `/proc/<pid>/maps`.
+#[derive(Serialize, Debug, Clone)]
+pub struct MapEntry {
+    pub start: u64,
+    pub end: u64,
+    pub readable: bool,
+    pub writable: bool,
+    pub executable: bool,
+    pub shared: bool,
+    pub offset: u64,
+    pub dev: String,
+    pub inode: u64,
+
+    /// The backing file or a pseudo-path such as `[heap]`; `None` for
+    /// anonymous mappings.
+    pub pathname: Option<String>,
+}
+
+/// Read and parse the maps of `pid`, or of this process if `None`.
+pub fn read_maps(pid: Option<u32>) -> anyhow::Result<Vec<MapEntry>> {
+    parse_maps(&fs::read_to_string(maps_path(pid))?)
+}
+
+fn maps_path(pid: Option<u32>) -> String {
+    match pid {
+        Some(pid) => format!("/proc/{}/maps", pid),
+        None => "/proc/self/maps".to_string(),
+    }
+}
+
+pub fn parse_maps(contents: &str) -> anyhow::Result<Vec<MapEntry>> {
+    contents.lines().map(parse_line).collect()
+}
+
+fn parse_line(line: &str) -> anyhow::Result<MapEntry> {
+    let invalid = || anyhow::anyhow!("Invalid maps line: {}", line);
+    let mut fields = line.splitn(6, ' ');
+    let mut next = || fields.next().ok_or_else(invalid);
+
+    let (start, end) = next()?.split_once('-').ok_or_else(invalid)?;
+    let perms = next()?.as_bytes();
+    if perms.len() != 4 {
+        return Err(invalid());
+    }
+    let offset = next()?;
+    let dev = next()?.to_string();
+    let inode = next()?.parse::<u64>().map_err(|_| invalid())?;
+    let pathname = fields.next().map(str::trim_start).filter(|p| !p.is_empty());
+
+    Ok(MapEntry {
+        start: u64::from_str_radix(start, 16).map_err(|_| invalid())?,
+        end: u64::from_str_radix(end, 16).map_err(|_| invalid())?,
+        readable: perms[0] == b'r',
+        writable: perms[1] == b'w',
+        executable: perms[2] == b'x',
+        shared: perms[3] == b's',
+        offset: u64::from_str_radix(offset, 16).map_err(|_| invalid())?,
+        dev,
+        inode,
+        pathname: pathname.map(String::from),
+    })
+}
+
+#[derive(Serialize, Debug)]
+pub struct MapsResult {
+    pub vmas: usize,
+    pub read_duration: Duration,
+    pub parse_duration: Duration,
+    pub threads: usize,
+    pub processes: usize,
+}
+
+/// Single page mappings that exist only to inflate the VMA count.
+struct ExtraVmas {
+    maps: Vec<NonNull<c_void>>,
+    page_size: usize,
+}
+
+impl ExtraVmas {
+    fn new(count: usize) -> anyhow::Result<Self> {
+        let page_size = rustix::param::page_size();
+        let mut vmas = ExtraVmas {
+            maps: Vec::with_capacity(count),
+            page_size,
+        };
+        for i in 0..count {
+            // Alternate protections so the kernel can't merge neighbouring
+            // mappings into one VMA.
+            let prot = if i % 2 == 0 {
+                ProtFlags::PROT_READ
+            } else {
+                ProtFlags::PROT_READ | ProtFlags::PROT_WRITE
+            };
+            let flags = MapFlags::MAP_PRIVATE | MapFlags::MAP_ANONYMOUS;
+            let map = unsafe { mmap_anonymous(None, page_size.try_into()?, prot, flags) }?;
+            vmas.maps.push(map);
+        }
+        Ok(vmas)
+    }
+}
+
+impl Drop for ExtraVmas {
+    fn drop(&mut self) {
+        for map in self.maps.drain(..) {
+            let _ = unsafe { munmap(map, self.page_size) };
+        }
+    }
+}
+
+pub fn run(
+    iterations: u64,
+    extra_vmas: usize,
+    threads: usize,
+    processes: usize,
+) -> anyhow::Result<Vec<MapsResult>> {
+    let _vmas = ExtraVmas::new(extra_vmas)?;
+    let path = maps_path(None);
+    (0..iterations)
+        .map(|_i| {
+            let start = Instant::now();
+            let contents = fs::read_to_string(&path)?;
+            let read_duration = start.elapsed();
+
+            let start = Instant::now();
+            let maps = parse_maps(&contents)?;
+            let parse_duration = start.elapsed();
+
+            Ok(MapsResult {
+                vmas: maps.len(),
+                read_duration,
+                parse_duration,
+                threads,
+                processes,
+            })
+        })
+        .collect()
+}
+
+pub fn print_summary(results: &[MapsResult]) {
+    let median = |f: fn(&MapsResult) -> Duration| {
+        let mut samples: Vec<f64> = results.iter().map(|r| f(r).as_nanos() as f64).collect();
+        samples.sort_by(f64::total_cmp);
+        percentile(&samples, 0.5) / 1000.0
+    };
+    let vmas = results.iter().map(|r| r.vmas).max().unwrap_or(0);
+    println!(
+        "{:>8} {:>18} {:>18}",
+        "VMAs", "Read median (us)", "Parse median (us)"
+    );
+    println!(
+        "{:>8} {:>18.2} {:>18.2}",
+        vmas,
+        median(|r| r.read_duration),
+        median(|r| r.parse_duration)
+    );
+}
@@ -0,0 +1,81 @@
+//! Helpers for reading `/proc/meminfo` and the similarly formatted
+//! `/proc/self/smaps_rollup`.
+
+use std::fs;
+
+/// Look up a `/proc/meminfo` field (e.g. `Hugepagesize`) and return it in bytes.
+pub fn meminfo_bytes(key: &str) -> Option<usize> {
+    field_bytes(&fs::read_to_string("/proc/meminfo").ok()?, key)
+}
+
+/// This process's resident set size in bytes, from `/proc/self/smaps_rollup`.
+/// Unlike `/proc/self/statm`, whose counters are batched per CPU and can lag
+/// by tens of pages, this walks the page tables, so it's exact.
+pub fn resident_bytes() -> anyhow::Result<usize> {
+    let rollup = fs::read_to_string("/proc/self/smaps_rollup")?;
+    field_bytes(&rollup, "Rss").ok_or_else(|| anyhow::anyhow!("smaps_rollup has no Rss field"))
+}
+
+const THP_SIZE_PATH: &str = "/sys/kernel/mm/transparent_hugepage/hpage_pmd_size";
+
+/// Transparent huge page usage of this process.
+#[derive(Debug)]
+pub struct ThpUsage {
+    /// Anonymous memory mapped by transparent huge pages.
+    anon_huge: usize,
+    /// All anonymous memory.
+    anon: usize,
+}
+
+impl ThpUsage {
+    pub fn sample() -> anyhow::Result<Self> {
+        let rollup = fs::read_to_string("/proc/self/smaps_rollup")?;
+        let field = |key| {
+            field_bytes(&rollup, key)
+                .ok_or_else(|| anyhow::anyhow!("smaps_rollup has no {} field", key))
+        };
+        Ok(ThpUsage {
+            anon_huge: field("AnonHugePages")?,
+            anon: field("Anonymous")?,
+        })
+    }
+
+    /// Estimate how many huge pages were split between `self` and `later`.
+    /// Freeing a huge page outright drops both counters by the same amount,
+    /// while splitting it drops only the huge page count (less whatever part
+    /// of it was also freed), so the unmatched part of the drop was split.
+    pub fn pages_split_since(&self, later: &ThpUsage) -> usize {
+        let huge_dropped = self.anon_huge.saturating_sub(later.anon_huge);
+        let freed = self.anon.saturating_sub(later.anon);
+        huge_dropped.saturating_sub(freed).div_ceil(thp_size())
+    }
+
+    /// Huge pages of `huge_page_size` currently mapped.
+    pub fn anon_huge_pages(&self, huge_page_size: usize) -> usize {
+        self.anon_huge / huge_page_size
+    }
+}
+
+/// The size of a transparent huge page, assuming 2MiB if it can't be read.
+pub fn thp_size() -> usize {
+    fs::read_to_string(THP_SIZE_PATH)
+        .ok()
+        .and_then(|size| size.trim().parse::<usize>().ok())
+        .unwrap_or(2 << 20)
+}
+
+fn field_bytes(contents: &str, key: &str) -> Option<usize> {
+    contents.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        if name != key {
+            return None;
+        }
+        let mut parts = value.split_whitespace();
+        let num = parts.next()?.parse::<usize>().ok()?;
+        match parts.next() {
+            Some("kB") => Some(num * 1024),
+            None => Some(num),
+            Some(_) => None,
+        }
+    })
+}
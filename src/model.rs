@@ -0,0 +1,180 @@
+//! A linear cost model of `PAGEMAP_SCAN`, fitted to scans of dirty sets that
+//! vary the region and page counts independently:
+//! `duration = fixed + per_region * regions + per_page * dirty_pages`.
+
+use crate::stats::percentile;
+use crate::{pagemap, BenchArgs, MemoryRegion};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// Dirty fractions and run lengths, in pages, of the fitted points. `None`
+/// dirties one contiguous prefix.
+const DIRTY_FRACTIONS: [f64; 4] = [0.125, 0.25, 0.5, 1.0];
+const RUN_LENGTHS: [Option<usize>; 4] = [Some(1), Some(8), Some(64), None];
+
+#[derive(Serialize, Debug)]
+pub struct ModelPoint {
+    pub dirty_fraction: f64,
+    pub dirty_run: Option<usize>,
+    pub regions: usize,
+    pub dirty_pages: usize,
+    pub scan_median: Duration,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ScanModel {
+    pub total_size: usize,
+    pub points: Vec<ModelPoint>,
+
+    /// Cost of a scan that finds nothing, the overhead floor of walking the
+    /// region.
+    pub fixed_ns: f64,
+    pub per_region_ns: f64,
+    pub per_page_ns: f64,
+
+    /// Fraction of the variance in the points' scan medians the model explains.
+    pub r_squared: f64,
+}
+
+/// Measure scans with nothing dirty and across the dirty fraction and run
+/// length grid, then fit the model to their medians by least squares.
+pub fn fit(args: &BenchArgs, iterations: u64) -> anyhow::Result<ScanModel> {
+    let clean = std::iter::once((0.0, None));
+    let grid = DIRTY_FRACTIONS
+        .iter()
+        .flat_map(|&fraction| RUN_LENGTHS.iter().map(move |&run| (fraction, run)));
+    let points = clean
+        .chain(grid)
+        .map(|(dirty_fraction, dirty_run)| {
+            let args = BenchArgs {
+                dirty_fraction,
+                dirty_run,
+                ..args.clone()
+            };
+            measure(&args, iterations)
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let rows: Vec<([f64; 3], f64)> = points
+        .iter()
+        .map(|p| {
+            (
+                [1.0, p.regions as f64, p.dirty_pages as f64],
+                p.scan_median.as_nanos() as f64,
+            )
+        })
+        .collect();
+    let [fixed_ns, per_region_ns, per_page_ns] = least_squares(&rows)
+        .ok_or_else(|| anyhow::anyhow!("Scan points don't determine a cost model"))?;
+
+    let predict = |x: &[f64; 3]| fixed_ns * x[0] + per_region_ns * x[1] + per_page_ns * x[2];
+    let mean = rows.iter().map(|(_, y)| y).sum::<f64>() / rows.len() as f64;
+    let residual: f64 = rows.iter().map(|(x, y)| (y - predict(x)).powi(2)).sum();
+    let total: f64 = rows.iter().map(|(_, y)| (y - mean).powi(2)).sum();
+
+    Ok(ScanModel {
+        total_size: args.total_size,
+        points,
+        fixed_ns,
+        per_region_ns,
+        per_page_ns,
+        r_squared: if total > 0.0 {
+            1.0 - residual / total
+        } else {
+            1.0
+        },
+    })
+}
+
+fn measure(args: &BenchArgs, iterations: u64) -> anyhow::Result<ModelPoint> {
+    let page_size = rustix::param::page_size();
+    let mut region = MemoryRegion::new(args, false)?;
+    region.make_dirty()?;
+    let mut regions = args.scan_buffer();
+
+    let mut region_count = 0;
+    let mut dirty_bytes = 0;
+    let mut durations = (0..iterations.max(1))
+        .map(|_i| {
+            region_count = 0;
+            dirty_bytes = 0;
+            let start = Instant::now();
+            pagemap::for_each_dirty_region(
+                region.ptr,
+                args.total_size,
+                &mut regions,
+                &args.scan_categories,
+                args.max_scan_resumes,
+                |dirty_regions| {
+                    region_count += dirty_regions.len();
+                    dirty_bytes += dirty_regions.iter().map(|r| r.end - r.start).sum::<u64>();
+                    Ok(())
+                },
+            )?;
+            Ok(start.elapsed().as_nanos() as f64)
+        })
+        .collect::<anyhow::Result<Vec<f64>>>()?;
+    durations.sort_by(f64::total_cmp);
+    region.release(args);
+
+    Ok(ModelPoint {
+        dirty_fraction: args.dirty_fraction,
+        dirty_run: args.dirty_run,
+        regions: region_count,
+        dirty_pages: dirty_bytes as usize / page_size,
+        scan_median: Duration::from_nanos(percentile(&durations, 0.5).round() as u64),
+    })
+}
+
+/// Solve the normal equations for the coefficients minimizing the squared
+/// error of `rows`, or `None` if the columns are linearly dependent.
+fn least_squares(rows: &[([f64; 3], f64)]) -> Option<[f64; 3]> {
+    // Augmented [XᵀX | Xᵀy], reduced by Gaussian elimination.
+    let mut m = [[0.0; 4]; 3];
+    for (x, y) in rows {
+        for i in 0..3 {
+            for j in 0..3 {
+                m[i][j] += x[i] * x[j];
+            }
+            m[i][3] += x[i] * y;
+        }
+    }
+    for col in 0..3 {
+        let pivot = (col..3).max_by(|&a, &b| m[a][col].abs().total_cmp(&m[b][col].abs()))?;
+        if m[pivot][col].abs() < f64::EPSILON * m[col][col].abs().max(1.0) {
+            return None;
+        }
+        m.swap(col, pivot);
+        let pivot_row = m[col];
+        for (i, row) in m.iter_mut().enumerate() {
+            if i != col {
+                let factor = row[col] / pivot_row[col];
+                for (value, pivot_value) in row.iter_mut().zip(pivot_row).skip(col) {
+                    *value -= factor * pivot_value;
+                }
+            }
+        }
+    }
+    Some([0, 1, 2].map(|i| m[i][3] / m[i][i]))
+}
+
+pub fn print_model(model: &ScanModel) {
+    println!(
+        "{:>10} {:>10} {:>10} {:>12} {:>16}",
+        "Dirty", "Run", "Regions", "Dirty pages", "Scan median (us)"
+    );
+    for p in &model.points {
+        println!(
+            "{:>9.1}% {:>10} {:>10} {:>12} {:>16.2}",
+            p.dirty_fraction * 100.0,
+            p.dirty_run.map_or("-".to_string(), |run| run.to_string()),
+            p.regions,
+            p.dirty_pages,
+            p.scan_median.as_nanos() as f64 / 1000.0
+        );
+    }
+    println!(
+        "\nscan = {:.1}ns + {:.3}ns/region + {:.3}ns/dirty page (R² = {:.3})",
+        model.fixed_ns, model.per_region_ns, model.per_page_ns, model.r_squared
+    );
+}
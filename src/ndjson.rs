@@ -0,0 +1,63 @@
+//! Streaming results as newline-delimited JSON while the strategies run, one
+//! result per line.
+//!
+//! Each worker serializes its results into its own buffer after an iteration
+//! is measured, and writes the buffer to stdout once it holds
+//! `--flush-interval` results, and again when its run ends. The measured
+//! durations never include serialization or I/O, but a write still happens
+//! between a worker's iterations, where it can wait on the stdout lock and
+//! disturb the caches of workers measuring at the same time. The interval
+//! trades latency for accuracy: `1` shows every result as soon as it's
+//! measured, while a larger interval writes less often, at the cost of
+//! results arriving in bursts and up to that many per worker being lost if
+//! the run is killed.
+
+use serde::Serialize;
+use std::io::{self, Write};
+
+/// How streamed results are batched.
+#[derive(Debug, Copy, Clone)]
+pub struct Stream {
+    pub flush_interval: usize,
+}
+
+/// One worker's results that have yet to be written.
+pub struct Batch {
+    lines: Vec<u8>,
+    pending: usize,
+    flush_interval: usize,
+}
+
+impl Batch {
+    pub fn new(stream: Stream) -> Self {
+        Batch {
+            lines: Vec::new(),
+            pending: 0,
+            flush_interval: stream.flush_interval,
+        }
+    }
+
+    pub fn push(&mut self, result: &impl Serialize) -> anyhow::Result<()> {
+        serde_json::to_writer(&mut self.lines, result)?;
+        self.lines.push(b'\n');
+        self.pending += 1;
+        if self.pending >= self.flush_interval {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Write out the buffered results in one go, so they aren't interleaved
+    /// with other workers' lines.
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        if self.pending == 0 {
+            return Ok(());
+        }
+        let mut out = io::stdout().lock();
+        out.write_all(&self.lines)?;
+        out.flush()?;
+        self.lines.clear();
+        self.pending = 0;
+        Ok(())
+    }
+}
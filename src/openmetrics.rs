@@ -0,0 +1,111 @@
+//! OpenMetrics text exposition of the per-strategy duration histograms, with
+//! exemplars tying each bucket back to a specific run and iteration.
+
+use crate::{BenchResult, Strategy};
+use std::io::Write;
+
+const METRIC: &str = "pagemap_scan_benchmark_duration_seconds";
+
+/// Upper bounds of the histogram buckets in nanoseconds, from 1us to about 16s
+/// in powers of 4. Kept as integers so the `le` labels are exact.
+fn bucket_bounds() -> impl Iterator<Item = u128> {
+    (0..13).map(|k| 1000 * 4u128.pow(k))
+}
+
+/// `nanos` as a decimal number of seconds, without trailing zeros.
+fn seconds_label(nanos: u128) -> String {
+    let label = format!("{}.{:09}", nanos / 1_000_000_000, nanos % 1_000_000_000);
+    label
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+pub fn write_openmetrics(
+    mut out: impl Write,
+    results: &[BenchResult],
+    run_id: &str,
+) -> anyhow::Result<()> {
+    writeln!(out, "# TYPE {} histogram", METRIC)?;
+    writeln!(out, "# UNIT {} seconds", METRIC)?;
+    writeln!(
+        out,
+        "# HELP {} Latency of one dirty-and-clear operation.",
+        METRIC
+    )?;
+
+    let mut strategies: Vec<Strategy> = Vec::new();
+    for result in results {
+        if !strategies.contains(&result.strategy) {
+            strategies.push(result.strategy);
+        }
+    }
+    for strategy in strategies {
+        let samples: Vec<&BenchResult> =
+            results.iter().filter(|r| r.strategy == strategy).collect();
+        let mut lower = None;
+        for (le, upper) in bucket_bounds()
+            .map(|b| (seconds_label(b), Some(b)))
+            .chain([("+Inf".to_string(), None)])
+        {
+            let within = |nanos: u128| upper.is_none_or(|upper| nanos <= upper);
+            let count = samples
+                .iter()
+                .filter(|r| within(r.duration.as_nanos()))
+                .count();
+            write!(
+                out,
+                "{}_bucket{{strategy=\"{:?}\",le=\"{}\"}} {}",
+                METRIC, strategy, le, count
+            )?;
+            // An exemplar must fall within its own bucket; the slowest one is
+            // the most interesting to chase down.
+            let exemplar = samples
+                .iter()
+                .filter(|r| {
+                    let nanos = r.duration.as_nanos();
+                    lower.is_none_or(|lower| nanos > lower) && within(nanos)
+                })
+                .max_by_key(|r| r.duration);
+            if let Some(r) = exemplar {
+                write!(
+                    out,
+                    " # {{trace_id=\"{}\",thread=\"{}\",iteration=\"{}\"}} {}",
+                    run_id,
+                    r.thread,
+                    r.iteration,
+                    r.duration.as_secs_f64()
+                )?;
+            }
+            writeln!(out)?;
+            lower = upper;
+        }
+        let sum: f64 = samples.iter().map(|r| r.duration.as_secs_f64()).sum();
+        writeln!(
+            out,
+            "{}_count{{strategy=\"{:?}\"}} {}",
+            METRIC,
+            strategy,
+            samples.len()
+        )?;
+        writeln!(out, "{}_sum{{strategy=\"{:?}\"}} {}", METRIC, strategy, sum)?;
+    }
+    writeln!(out, "# EOF")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bucket_bounds, seconds_label};
+
+    #[test]
+    fn bucket_labels_are_exact_decimals() {
+        let labels: Vec<String> = bucket_bounds().map(seconds_label).collect();
+        assert_eq!(labels[0], "0.000001");
+        assert_eq!(labels[5], "0.001024");
+        assert_eq!(labels[10], "1.048576");
+        assert_eq!(labels[12], "16.777216");
+        assert_eq!(seconds_label(300_000_000), "0.3");
+        assert_eq!(seconds_label(2_000_000_000), "2");
+    }
+}
@@ -2,58 +2,290 @@
 //!
 //! For other platforms, a no-op implementation is provided.
 
-pub use internal::dirty_pages_in_region;
-use std::fmt;
-
-#[allow(dead_code)]
-#[derive(Debug)]
-pub struct DirtyPages<'a> {
-    /// Slice into the initialized portion of region_storage
-    pub regions: &'a [PageRegion],
-
-    /// The number of bytes checked in the pagemap. Might be less than `len`, in which case
-    /// the pages beyond `checked_bytes` should be treated as potentially dirty.
-    pub checked_bytes: usize,
+pub mod raw;
+
+pub use internal::{dirty_pages_in_region, dirty_pages_in_region_and_reset, open_process_pagemap};
+use nix::sys::mman::{mmap_anonymous, munmap, MapFlags, ProtFlags};
+pub use raw::{DirtyPages, PageCategories, PageRegion, ScanCategories};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::ffi::c_void;
+use std::fs::File;
+use std::mem::MaybeUninit;
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::ptr::NonNull;
+use std::slice;
+
+/// Serialized here rather than derived so that [`raw`] stays free of serde.
+impl Serialize for PageRegion {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let categories: Vec<String> = self
+            .categories()
+            .iter_names()
+            .map(|(name, _)| name.to_lowercase())
+            .collect();
+        let mut region = serializer.serialize_struct("PageRegion", 4)?;
+        region.serialize_field("start", &self.start)?;
+        region.serialize_field("end", &self.end)?;
+        region.serialize_field("length", &(self.end - self.start))?;
+        region.serialize_field("categories", &categories)?;
+        region.end()
+    }
 }
 
-#[repr(C)]
-#[derive(Debug, Copy, Clone)]
-pub struct PageRegion {
-    pub start: u64,
-    pub end: u64,
-    categories: Categories,
+impl ScanCategories {
+    /// Build categories from comma separated lists of category names such as
+    /// `written,!file,!pfnzero`, where a `!` prefix matches pages *without*
+    /// the category. Lists that aren't given keep their defaults.
+    pub fn parse(mask: Option<&str>, anyof: Option<&str>) -> anyhow::Result<Self> {
+        let mut categories = ScanCategories::default();
+        let mut mask_inverted = categories.inverted;
+        let mut anyof_inverted = PageCategories::empty();
+        if let Some(mask) = mask {
+            (categories.mask, mask_inverted) = parse_category_list(mask)?;
+        }
+        if let Some(anyof) = anyof {
+            (categories.anyof, anyof_inverted) = parse_category_list(anyof)?;
+        }
+
+        // Inversion applies to the page's categories as a whole, so a category
+        // can't be required in one list and negated in the other.
+        let conflicting = (categories.mask & anyof_inverted & !mask_inverted)
+            | (categories.anyof & mask_inverted & !anyof_inverted);
+        if !conflicting.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Scan categories both required and negated: {}",
+                conflicting
+            ));
+        }
+        categories.inverted = mask_inverted | anyof_inverted;
+        Ok(categories)
+    }
 }
 
-bitflags::bitflags! {
-    #[derive(Copy, Clone)]
-    #[repr(transparent)]
-    struct Categories: u64 {
-        const WPALLOWED = 1 << 0;
-        const WRITTEN = 1 << 1;
-        const FILE = 1 << 2;
-        const PRESENT = 1 << 3;
-        const SWAPPED = 1 << 4;
-        const PFNZERO = 1 << 5;
-        const HUGE = 1 << 6;
-        const SOFT_DIRTY = 1 << 7;
+/// Scan `[base, base + len)` with as many ioctl calls as it takes, since a
+/// single call may stop early (e.g. once `regions_buffer` fills up). `f` is
+/// called with each batch of regions. Fails if the scan needs more than
+/// `max_resumes` calls after the first. Returns the number of resumes used.
+pub fn for_each_dirty_region(
+    base: *const u8,
+    len: usize,
+    regions_buffer: &mut [MaybeUninit<PageRegion>],
+    categories: &ScanCategories,
+    max_resumes: Option<usize>,
+    f: impl FnMut(&[PageRegion]) -> anyhow::Result<()>,
+) -> anyhow::Result<usize> {
+    resume_scan(
+        dirty_pages_in_region,
+        base,
+        len,
+        regions_buffer,
+        categories,
+        max_resumes,
+        f,
+    )
+}
+
+/// [`for_each_dirty_region`] using [`dirty_pages_in_region_and_reset`].
+pub fn for_each_dirty_region_and_reset(
+    base: *const u8,
+    len: usize,
+    regions_buffer: &mut [MaybeUninit<PageRegion>],
+    categories: &ScanCategories,
+    max_resumes: Option<usize>,
+    f: impl FnMut(&[PageRegion]) -> anyhow::Result<()>,
+) -> anyhow::Result<usize> {
+    resume_scan(
+        dirty_pages_in_region_and_reset,
+        base,
+        len,
+        regions_buffer,
+        categories,
+        max_resumes,
+        f,
+    )
+}
+
+/// [`for_each_dirty_region`] over another process's memory, through
+/// `pagemap`, its `/proc/<pid>/pagemap` from [`open_process_pagemap`].
+pub fn for_each_dirty_region_in(
+    pagemap: &File,
+    base: *const u8,
+    len: usize,
+    regions_buffer: &mut [MaybeUninit<PageRegion>],
+    categories: &ScanCategories,
+    max_resumes: Option<usize>,
+    f: impl FnMut(&[PageRegion]) -> anyhow::Result<()>,
+) -> anyhow::Result<usize> {
+    resume_scan(
+        |base, len, regions_buffer, categories| {
+            internal::dirty_pages_in_process(pagemap, base, len, regions_buffer, categories)
+        },
+        base,
+        len,
+        regions_buffer,
+        categories,
+        max_resumes,
+        f,
+    )
+}
+
+/// An anonymous read-write mapping owned by [`scan_scope`].
+pub struct ScopedMapping {
+    ptr: NonNull<c_void>,
+    len: usize,
+}
+
+impl ScopedMapping {
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.ptr.as_ptr() as *mut u8
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// The mapping's bytes, for dirtying it.
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for ScopedMapping {
+    fn drop(&mut self) {
+        let _ = unsafe { munmap(self.ptr, self.len) };
     }
 }
 
-impl fmt::Debug for Categories {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        bitflags::parser::to_writer(self, f)
+/// Map `len` bytes of anonymous memory, run `f` on them, and unmap them again
+/// once `f` returns.
+///
+/// The unmap happens in a destructor, so it also runs while unwinding from a
+/// panic in `f`, and the mapping doesn't outlive a caught panic. With
+/// `panic = "abort"` there is no unwinding: the process ends at the panic and
+/// the kernel reclaims the mapping along with everything else.
+pub fn scan_scope<R>(len: usize, f: impl FnOnce(&mut ScopedMapping) -> R) -> anyhow::Result<R> {
+    let ptr = unsafe {
+        mmap_anonymous(
+            None,
+            NonZeroUsize::new(len).ok_or_else(|| anyhow::anyhow!("Can't map 0 bytes"))?,
+            ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+            MapFlags::MAP_PRIVATE | MapFlags::MAP_ANONYMOUS,
+        )
+    }?;
+    let mut mapping = ScopedMapping { ptr, len };
+    Ok(f(&mut mapping))
+}
+
+/// Zero the pages of `[ptr, ptr + len)` that match `categories`, returning the
+/// address ranges that were cleared, in ascending order.
+///
+/// # Safety
+///
+/// `[ptr, ptr + len)` must be a single writable mapping owned by the caller,
+/// starting on a page boundary. Nothing else may read or write it during the
+/// call, and no Rust references into it may be live, since any page of it may
+/// be overwritten.
+pub unsafe fn clear_dirty(
+    ptr: *mut u8,
+    len: usize,
+    categories: &ScanCategories,
+) -> anyhow::Result<Vec<Range<usize>>> {
+    let page_size = rustix::param::page_size();
+    let mut regions_buffer: Box<[MaybeUninit<PageRegion>]> =
+        Box::new_uninit_slice(len.div_ceil(page_size));
+    let mut cleared = Vec::new();
+    for_each_dirty_region(ptr, len, &mut regions_buffer, categories, None, |regions| {
+        for region in regions {
+            let range = usize::try_from(region.start)?..usize::try_from(region.end)?;
+            unsafe { slice::from_raw_parts_mut(range.start as *mut u8, range.len()) }.fill(0);
+            cleared.push(range);
+        }
+        Ok(())
+    })?;
+    Ok(cleared)
+}
+
+fn resume_scan(
+    mut scan: impl for<'a> FnMut(
+        *const u8,
+        usize,
+        &'a mut [MaybeUninit<PageRegion>],
+        &ScanCategories,
+    ) -> anyhow::Result<DirtyPages<'a>>,
+    base: *const u8,
+    len: usize,
+    regions_buffer: &mut [MaybeUninit<PageRegion>],
+    categories: &ScanCategories,
+    max_resumes: Option<usize>,
+    mut f: impl FnMut(&[PageRegion]) -> anyhow::Result<()>,
+) -> anyhow::Result<usize> {
+    let mut checked = 0;
+    let mut calls = 0;
+    while checked < len {
+        if let Some(max) = max_resumes.filter(|&max| calls > max) {
+            return Err(anyhow::anyhow!(
+                "pagemap_scan didn't finish within {} resumes ({} of {} bytes checked)",
+                max,
+                checked,
+                len
+            ));
+        }
+        let start = base.wrapping_add(checked);
+        let dirty = scan(start, len - checked, regions_buffer, categories)?;
+        calls += 1;
+        f(dirty.regions)?;
+
+        // The reported walk end can trail the regions already returned (seen
+        // with many small VMAs), so never resume before the last region's end.
+        let reported_end = dirty
+            .regions
+            .last()
+            .map_or(0, |r| (r.end - start as u64) as usize);
+        let progress = dirty.checked_bytes.max(reported_end);
+        if progress == 0 {
+            return Err(anyhow::anyhow!("pagemap_scan made no progress"));
+        }
+        // A partial final page is walked in full, so this may overshoot `len`.
+        checked += progress;
     }
+    Ok(calls.saturating_sub(1))
 }
-impl fmt::Display for Categories {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        bitflags::parser::to_writer(self, f)
+
+/// Parse `written,!file,...` into the listed categories and the subset of them
+/// that are negated.
+fn parse_category_list(list: &str) -> anyhow::Result<(PageCategories, PageCategories)> {
+    let mut categories = PageCategories::empty();
+    let mut inverted = PageCategories::empty();
+    for name in list.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        let (negated, name) = match name.strip_prefix('!') {
+            Some(name) => (true, name),
+            None => (false, name),
+        };
+        let category = PageCategories::from_name(&name.to_uppercase().replace('-', "_"))
+            .ok_or_else(|| {
+                let valid: Vec<String> = PageCategories::all()
+                    .iter_names()
+                    .map(|(name, _)| name.to_lowercase())
+                    .collect();
+                anyhow::anyhow!(
+                    "Unknown page category: {}. Use one of: {}",
+                    name,
+                    valid.join(", ")
+                )
+            })?;
+        categories |= category;
+        if negated {
+            inverted |= category;
+        }
     }
+    Ok((categories, inverted))
 }
 
 #[cfg(not(target_os = "linux"))]
 mod internal {
-    use super::{DirtyPages, PageRegion};
-    use crate::prelude::*;
+    use super::{DirtyPages, PageRegion, ScanCategories};
+    use std::fs::File;
     use std::mem::MaybeUninit;
 
     #[allow(unused_variables)]
@@ -61,6 +293,39 @@ mod internal {
         base: *const u8,
         len: usize,
         regions_buffer: &mut [MaybeUninit<PageRegion>],
+        categories: &ScanCategories,
+    ) -> anyhow::Result<DirtyPages<'a>> {
+        Err(anyhow::anyhow!(
+            "pagemap_scan ioctl not supported on this platform"
+        ))
+    }
+
+    #[allow(unused_variables)]
+    pub fn dirty_pages_in_region_and_reset<'a>(
+        base: *const u8,
+        len: usize,
+        regions_buffer: &mut [MaybeUninit<PageRegion>],
+        categories: &ScanCategories,
+    ) -> anyhow::Result<DirtyPages<'a>> {
+        Err(anyhow::anyhow!(
+            "pagemap_scan ioctl not supported on this platform"
+        ))
+    }
+
+    #[allow(unused_variables)]
+    pub fn open_process_pagemap(pid: u32) -> anyhow::Result<File> {
+        Err(anyhow::anyhow!(
+            "pagemap_scan ioctl not supported on this platform"
+        ))
+    }
+
+    #[allow(unused_variables)]
+    pub fn dirty_pages_in_process<'a>(
+        pagemap: &File,
+        base: *const u8,
+        len: usize,
+        regions_buffer: &mut [MaybeUninit<PageRegion>],
+        categories: &ScanCategories,
     ) -> anyhow::Result<DirtyPages<'a>> {
         Err(anyhow::anyhow!(
             "pagemap_scan ioctl not supported on this platform"
@@ -70,19 +335,92 @@ mod internal {
 
 #[cfg(target_os = "linux")]
 mod internal {
-    use super::{Categories, DirtyPages, PageRegion};
-    use rustix::ioctl::{ioctl, opcode, Ioctl, IoctlOutput, Opcode};
+    use super::raw::{self, ScanFlags};
+    use super::{DirtyPages, PageRegion, ScanCategories};
+    use std::fmt;
     use std::fs::File;
     use std::mem::MaybeUninit;
-    use std::os::raw::c_void;
     use std::sync::LazyLock;
-    use std::{fmt, ptr};
 
-    pub fn dirty_pages_in_region(
+    pub fn dirty_pages_in_region<'a>(
         base: *const u8,
         len: usize,
-        regions_buffer: &mut [MaybeUninit<PageRegion>],
-    ) -> anyhow::Result<DirtyPages> {
+        regions_buffer: &'a mut [MaybeUninit<PageRegion>],
+        categories: &ScanCategories,
+    ) -> anyhow::Result<DirtyPages<'a>> {
+        scan(base, len, regions_buffer, categories, ScanFlags::empty())
+    }
+
+    /// Like [`dirty_pages_in_region`], but also write-protects the matched pages
+    /// so that the next scan only reports pages written since this one.
+    ///
+    /// The region must be registered for asynchronous uffd-wp (see
+    /// [`crate::uffd::WriteProtect`]), otherwise the ioctl fails.
+    pub fn dirty_pages_in_region_and_reset<'a>(
+        base: *const u8,
+        len: usize,
+        regions_buffer: &'a mut [MaybeUninit<PageRegion>],
+        categories: &ScanCategories,
+    ) -> anyhow::Result<DirtyPages<'a>> {
+        scan(
+            base,
+            len,
+            regions_buffer,
+            categories,
+            ScanFlags::WP_MATCHING | ScanFlags::CHECK_WPASYNC,
+        )
+    }
+
+    /// Open `/proc/<pid>/pagemap` for scanning another process, checking that
+    /// the `pagemap_scan` ioctl works on it.
+    pub fn open_process_pagemap(pid: u32) -> anyhow::Result<File> {
+        let path = format!("/proc/{}/pagemap", pid);
+        let file = File::open(&path).map_err(|e| anyhow::anyhow!("Can't open {}: {}", path, e))?;
+        raw::probe(&file).map_err(|e| anyhow::anyhow!("{}: {}", path, e))?;
+        Ok(file)
+    }
+
+    /// Like [`dirty_pages_in_region`], but for `[base, base + len)` in the
+    /// process whose pagemap is `pagemap`.
+    pub fn dirty_pages_in_process<'a>(
+        pagemap: &File,
+        base: *const u8,
+        len: usize,
+        regions_buffer: &'a mut [MaybeUninit<PageRegion>],
+        categories: &ScanCategories,
+    ) -> anyhow::Result<DirtyPages<'a>> {
+        scan_through(
+            pagemap,
+            base,
+            len,
+            regions_buffer,
+            categories,
+            ScanFlags::empty(),
+        )
+    }
+
+    fn scan<'a>(
+        base: *const u8,
+        len: usize,
+        regions_buffer: &'a mut [MaybeUninit<PageRegion>],
+        categories: &ScanCategories,
+        flags: ScanFlags,
+    ) -> anyhow::Result<DirtyPages<'a>> {
+        let pagemap = match &*PAGEMAP {
+            Some(pagemap) => pagemap,
+            None => return Err(anyhow::anyhow!("pagemap_scan ioctl not supported")),
+        };
+        scan_through(pagemap, base, len, regions_buffer, categories, flags)
+    }
+
+    fn scan_through<'a>(
+        pagemap: &File,
+        base: *const u8,
+        len: usize,
+        regions_buffer: &'a mut [MaybeUninit<PageRegion>],
+        categories: &ScanCategories,
+        flags: ScanFlags,
+    ) -> anyhow::Result<DirtyPages<'a>> {
         if len == 0 || regions_buffer.is_empty() {
             return Ok(DirtyPages {
                 regions: &[],
@@ -90,32 +428,14 @@ mod internal {
             });
         }
 
-        let pagemap = match &*PAGEMAP {
-            Some(pagemap) => pagemap,
-            None => return Err(anyhow::anyhow!("pagemap_scan ioctl not supported")),
-        };
-
-        let scan_arg = PageMapScan::new(
-            ptr::slice_from_raw_parts(base, len),
+        Ok(raw::scan(
+            pagemap,
+            base,
+            len,
             regions_buffer,
-            regions_buffer.len(),
-        );
-        let result = unsafe { ioctl(pagemap, scan_arg) };
-        match result {
-            Ok(result) => {
-                let regions = unsafe {
-                    std::slice::from_raw_parts(
-                        regions_buffer.as_ptr() as *const PageRegion,
-                        result.regions_count,
-                    )
-                };
-                Ok(DirtyPages {
-                    regions,
-                    checked_bytes: result.walk_end - base as usize,
-                })
-            }
-            Err(_) => Err(anyhow::anyhow!("pagemap_scan ioctl failed")),
-        }
+            categories,
+            flags,
+        )?)
     }
 
     bitflags::bitflags! {
@@ -137,88 +457,6 @@ mod internal {
         }
     }
 
-    struct PageMapScan<'a> {
-        pm_scan_arg: pm_scan_arg,
-        _regions: &'a mut [MaybeUninit<PageRegion>],
-    }
-
-    impl<'a> PageMapScan<'a> {
-        fn new(
-            region: *const [u8],
-            regions: &'a mut [MaybeUninit<PageRegion>],
-            max_pages: usize,
-        ) -> PageMapScan<'a> {
-            PageMapScan {
-                pm_scan_arg: pm_scan_arg {
-                    size: size_of::<pm_scan_arg>() as u64,
-                    flags: 0,
-                    start: unsafe { (*region).as_ptr() as u64 },
-                    end: unsafe { (*region).as_ptr().wrapping_add(region.len()) as u64 },
-                    walk_end: 0,
-                    vec: regions.as_mut_ptr() as u64,
-                    vec_len: regions.len() as u64,
-                    max_pages: max_pages as u64,
-                    category_inverted: Categories::FILE | Categories::PFNZERO,
-                    category_anyof_mask: Categories::empty(),
-                    category_mask: Categories::WRITTEN | Categories::FILE | Categories::PFNZERO,
-                    return_mask: Categories::all(),
-                },
-                _regions: regions,
-            }
-        }
-    }
-
-    #[derive(Debug)]
-    #[allow(dead_code)]
-    struct PageMapScanResult {
-        walk_end: usize,
-        regions_count: usize,
-    }
-
-    #[repr(C)]
-    struct pm_scan_arg {
-        size: u64,
-        flags: u64,
-        start: u64,
-        end: u64,
-        walk_end: u64,
-        vec: u64,
-        vec_len: u64,
-        max_pages: u64,
-        category_inverted: Categories,
-        category_mask: Categories,
-        category_anyof_mask: Categories,
-        return_mask: Categories,
-    }
-
-    const PAGEMAP_SCAN: Opcode = opcode::read_write::<pm_scan_arg>(b'f', 16);
-
-    unsafe impl<'a> Ioctl for PageMapScan<'a> {
-        type Output = PageMapScanResult;
-
-        const IS_MUTATING: bool = false;
-
-        fn opcode(&self) -> Opcode {
-            PAGEMAP_SCAN
-        }
-
-        fn as_ptr(&mut self) -> *mut c_void {
-            (&raw mut self.pm_scan_arg).cast()
-        }
-
-        unsafe fn output_from_ptr(
-            out: IoctlOutput,
-            extract_output: *mut c_void,
-        ) -> rustix::io::Result<Self::Output> {
-            let extract_output = extract_output.cast::<pm_scan_arg>();
-            let len = usize::try_from(out).unwrap();
-            Ok(PageMapScanResult {
-                regions_count: len,
-                walk_end: unsafe { (*extract_output).walk_end.try_into().unwrap() },
-            })
-        }
-    }
-
     /// A static reference to the `/proc/self/pagemap` file. `None` if the file
     /// can't be opened, or if the `pagemap_scan` ioctl is not supported.
     static PAGEMAP: LazyLock<Option<File>> = LazyLock::new(|| {
@@ -228,10 +466,8 @@ mod internal {
         }
         let file = file.unwrap();
         // Check if the `pagemap_scan` ioctl is supported.
-        let mut regions = vec![MaybeUninit::<PageRegion>::uninit(); 0];
-        let pm_scan = PageMapScan::new(ptr::slice_from_raw_parts(ptr::null(), 0), &mut regions, 0);
-        match unsafe { ioctl(&file, pm_scan) } {
-            Ok(_) => Some(file),
+        match raw::probe(&file) {
+            Ok(()) => Some(file),
             Err(_) => None,
         }
     });
@@ -0,0 +1,248 @@
+//! The `PAGEMAP_SCAN` ioctl itself: argument marshalling and a single call.
+//!
+//! This only uses `core`, `rustix` and `bitflags`, and reports failures with
+//! the plain [`ScanError`], so it can be embedded without the benchmark's
+//! dependencies. The parent module layers `anyhow` errors, the shared
+//! `/proc/self/pagemap` handle and resumed scans on top.
+
+use core::fmt;
+
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct DirtyPages<'a> {
+    /// Slice into the initialized portion of region_storage
+    pub regions: &'a [PageRegion],
+
+    /// The number of bytes checked in the pagemap. Might be less than `len`, in which case
+    /// the pages beyond `checked_bytes` should be treated as potentially dirty.
+    pub checked_bytes: usize,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct PageRegion {
+    pub start: u64,
+    pub end: u64,
+    categories: PageCategories,
+}
+
+impl PageRegion {
+    /// The categories of the region's pages, limited to the scan's return mask.
+    pub fn categories(&self) -> PageCategories {
+        self.categories
+    }
+}
+
+bitflags::bitflags! {
+    #[derive(Copy, Clone, PartialEq, Eq)]
+    #[repr(transparent)]
+    pub struct PageCategories: u64 {
+        const WPALLOWED = 1 << 0;
+        const WRITTEN = 1 << 1;
+        const FILE = 1 << 2;
+        const PRESENT = 1 << 3;
+        const SWAPPED = 1 << 4;
+        const PFNZERO = 1 << 5;
+        const HUGE = 1 << 6;
+        const SOFT_DIRTY = 1 << 7;
+    }
+}
+
+impl fmt::Debug for PageCategories {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        bitflags::parser::to_writer(self, f)
+    }
+}
+impl fmt::Display for PageCategories {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        bitflags::parser::to_writer(self, f)
+    }
+}
+
+/// Which pages a scan matches. A page's categories are first XORed with
+/// `inverted`; the page then matches if it has every category in `mask` and,
+/// when `anyof` is non-empty, at least one category in `anyof`.
+#[derive(Debug, Copy, Clone)]
+pub struct ScanCategories {
+    pub inverted: PageCategories,
+    pub mask: PageCategories,
+    pub anyof: PageCategories,
+}
+
+impl Default for ScanCategories {
+    /// Written anonymous pages that aren't mapped to the zero page. Outside
+    /// uffd-wp tracking the kernel also reports never-touched pages (and guard
+    /// pages) as written, so the pages must also be present or swapped.
+    fn default() -> Self {
+        ScanCategories {
+            inverted: PageCategories::FILE | PageCategories::PFNZERO,
+            mask: PageCategories::WRITTEN | PageCategories::FILE | PageCategories::PFNZERO,
+            anyof: PageCategories::PRESENT | PageCategories::SWAPPED,
+        }
+    }
+}
+
+/// A failed `PAGEMAP_SCAN` call, e.g. `EINVAL` from a kernel without it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ScanError(pub rustix::io::Errno);
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "pagemap_scan ioctl failed: {}", self.0)
+    }
+}
+
+impl core::error::Error for ScanError {}
+
+#[cfg(target_os = "linux")]
+pub use linux::{probe, scan, ScanFlags};
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::{DirtyPages, PageCategories, PageRegion, ScanCategories, ScanError};
+    use core::ffi::c_void;
+    use core::mem::MaybeUninit;
+    use core::ptr;
+    use rustix::fd::AsFd;
+    use rustix::ioctl::{ioctl, opcode, Ioctl, IoctlOutput, Opcode};
+
+    bitflags::bitflags! {
+        #[derive(Copy, Clone, Debug)]
+        #[repr(transparent)]
+        pub struct ScanFlags: u64 {
+            const WP_MATCHING = 1 << 0;
+            const CHECK_WPASYNC = 1 << 1;
+        }
+    }
+
+    struct PageMapScan<'a> {
+        pm_scan_arg: pm_scan_arg,
+        _regions: &'a mut [MaybeUninit<PageRegion>],
+    }
+
+    impl<'a> PageMapScan<'a> {
+        fn new(
+            region: *const [u8],
+            regions: &'a mut [MaybeUninit<PageRegion>],
+            max_pages: usize,
+            categories: &ScanCategories,
+            flags: ScanFlags,
+        ) -> PageMapScan<'a> {
+            PageMapScan {
+                pm_scan_arg: pm_scan_arg {
+                    size: size_of::<pm_scan_arg>() as u64,
+                    flags: flags.bits(),
+                    start: unsafe { (*region).as_ptr() as u64 },
+                    end: unsafe { (*region).as_ptr().wrapping_add(region.len()) as u64 },
+                    walk_end: 0,
+                    vec: regions.as_mut_ptr() as u64,
+                    vec_len: regions.len() as u64,
+                    max_pages: max_pages as u64,
+                    category_inverted: categories.inverted,
+                    category_anyof_mask: categories.anyof,
+                    category_mask: categories.mask,
+                    return_mask: PageCategories::all(),
+                },
+                _regions: regions,
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    struct PageMapScanResult {
+        walk_end: usize,
+        regions_count: usize,
+    }
+
+    #[repr(C)]
+    struct pm_scan_arg {
+        size: u64,
+        flags: u64,
+        start: u64,
+        end: u64,
+        walk_end: u64,
+        vec: u64,
+        vec_len: u64,
+        max_pages: u64,
+        category_inverted: PageCategories,
+        category_mask: PageCategories,
+        category_anyof_mask: PageCategories,
+        return_mask: PageCategories,
+    }
+
+    const PAGEMAP_SCAN: Opcode = opcode::read_write::<pm_scan_arg>(b'f', 16);
+
+    unsafe impl<'a> Ioctl for PageMapScan<'a> {
+        type Output = PageMapScanResult;
+
+        const IS_MUTATING: bool = false;
+
+        fn opcode(&self) -> Opcode {
+            PAGEMAP_SCAN
+        }
+
+        fn as_ptr(&mut self) -> *mut c_void {
+            (&raw mut self.pm_scan_arg).cast()
+        }
+
+        unsafe fn output_from_ptr(
+            out: IoctlOutput,
+            extract_output: *mut c_void,
+        ) -> rustix::io::Result<Self::Output> {
+            let extract_output = extract_output.cast::<pm_scan_arg>();
+            let len = usize::try_from(out).unwrap();
+            Ok(PageMapScanResult {
+                regions_count: len,
+                walk_end: unsafe { (*extract_output).walk_end.try_into().unwrap() },
+            })
+        }
+    }
+
+    /// Scan `[base, base + len)` once through `pagemap`, an open
+    /// `/proc/<pid>/pagemap`, filling `regions_buffer` with matching regions.
+    /// The walk may stop short of `len`; see [`DirtyPages::checked_bytes`].
+    pub fn scan<'a>(
+        pagemap: impl AsFd,
+        base: *const u8,
+        len: usize,
+        regions_buffer: &'a mut [MaybeUninit<PageRegion>],
+        categories: &ScanCategories,
+        flags: ScanFlags,
+    ) -> Result<DirtyPages<'a>, ScanError> {
+        let scan_arg = PageMapScan::new(
+            ptr::slice_from_raw_parts(base, len),
+            regions_buffer,
+            // Only the buffer's capacity should stop the walk early; a page
+            // limit would also cut short walks that fit in one region.
+            0,
+            categories,
+            flags,
+        );
+        let result = unsafe { ioctl(pagemap, scan_arg) }.map_err(ScanError)?;
+        let regions = unsafe {
+            core::slice::from_raw_parts(
+                regions_buffer.as_ptr() as *const PageRegion,
+                result.regions_count,
+            )
+        };
+        Ok(DirtyPages {
+            regions,
+            checked_bytes: result.walk_end - base as usize,
+        })
+    }
+
+    /// Check that `pagemap` supports `PAGEMAP_SCAN` with an empty scan.
+    pub fn probe(pagemap: impl AsFd) -> Result<(), ScanError> {
+        let mut regions: [MaybeUninit<PageRegion>; 0] = [];
+        let pm_scan = PageMapScan::new(
+            ptr::slice_from_raw_parts(ptr::null(), 0),
+            &mut regions,
+            0,
+            &ScanCategories::default(),
+            ScanFlags::empty(),
+        );
+        unsafe { ioctl(pagemap, pm_scan) }.map_err(ScanError)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+//! The cost of establishing a dirty resident set: faulting each page in by
+//! writing to it, against MADV_POPULATE_WRITE faulting them all in one call.
+
+use crate::stats::percentile;
+use crate::{BenchArgs, MemoryRegion};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+#[derive(Serialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Method {
+    /// Write to every dirtied page, taking a fault on each.
+    FirstTouch,
+    /// MADV_POPULATE_WRITE each run of dirtied pages.
+    PopulateWrite,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PopulateResult {
+    pub method: Method,
+    pub total_size: usize,
+    pub dirty_fraction: f64,
+    pub duration: Duration,
+    pub threads: usize,
+    pub processes: usize,
+}
+
+/// Dirty a freshly mapped region with each method in turn, so every page
+/// faults in from scratch.
+pub fn run(args: &BenchArgs, iterations: u64) -> anyhow::Result<Vec<PopulateResult>> {
+    let mut results = Vec::new();
+    for _ in 0..iterations {
+        for method in [Method::FirstTouch, Method::PopulateWrite] {
+            let args = BenchArgs {
+                populate_write: method == Method::PopulateWrite,
+                ..args.clone()
+            };
+            let mut region = MemoryRegion::new(&args, false)?;
+            let start = Instant::now();
+            region.make_dirty()?;
+            let duration = start.elapsed();
+            region.release(&args);
+
+            results.push(PopulateResult {
+                method,
+                total_size: args.total_size,
+                dirty_fraction: args.dirty_fraction,
+                duration,
+                threads: args.threads,
+                processes: args.processes,
+            });
+        }
+    }
+    Ok(results)
+}
+
+pub fn print_summary(results: &[PopulateResult]) {
+    let page_size = rustix::param::page_size();
+    println!(
+        "{:<16} {:>8} {:>12} {:>12} {:>12}",
+        "Method", "Samples", "p50 (us)", "p90 (us)", "ns/page"
+    );
+    for method in [Method::FirstTouch, Method::PopulateWrite] {
+        let runs: Vec<&PopulateResult> = results.iter().filter(|r| r.method == method).collect();
+        if runs.is_empty() {
+            continue;
+        }
+        let mut durations: Vec<f64> = runs.iter().map(|r| r.duration.as_nanos() as f64).collect();
+        durations.sort_by(f64::total_cmp);
+        let dirty_bytes = (runs[0].total_size as f64 * runs[0].dirty_fraction).round() as usize;
+        let median = percentile(&durations, 0.5);
+        println!(
+            "{:<16} {:>8} {:>12.2} {:>12.2} {:>12.1}",
+            format!("{:?}", method),
+            runs.len(),
+            median / 1000.0,
+            percentile(&durations, 0.9) / 1000.0,
+            median / dirty_bytes.div_ceil(page_size).max(1) as f64
+        );
+    }
+}
@@ -0,0 +1,440 @@
+//! Per-strategy summary statistics over the raw benchmark results.
+
+use crate::{BenchResult, Strategy};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use std::time::Duration;
+
+#[derive(Serialize, Debug)]
+pub struct StrategySummary {
+    pub strategy: Strategy,
+    pub samples: usize,
+    pub min: Duration,
+    pub median: Duration,
+    pub mean: Duration,
+    pub max: Duration,
+
+    /// 95% bootstrap confidence interval for the median, if requested.
+    pub median_ci: Option<(Duration, Duration)>,
+
+    /// Median time until deferred clearing completed, for strategies that
+    /// clear in the background.
+    pub background_median: Option<Duration>,
+
+    /// 95% bootstrap confidence interval for `background_median`, if
+    /// requested.
+    pub background_ci: Option<(Duration, Duration)>,
+
+    /// Median full map-to-unmap cycle, with `--churn`.
+    pub cycle_median: Option<Duration>,
+
+    /// Median scan throughput in pages walked and regions reported per
+    /// second, for scan-based strategies.
+    pub scan_pages_per_sec: Option<f64>,
+    pub scan_regions_per_sec: Option<f64>,
+
+    /// Median cost per dirtied page, for comparing runs of different sizes.
+    /// `None` when nothing was dirtied.
+    pub ns_per_dirty_page: Option<f64>,
+
+    /// Median clear phase cost per byte the strategy cleared: the whole
+    /// region for full-region strategies, what the scan found (or the part of
+    /// it selected for clearing) for scan-based ones. `None` when nothing was
+    /// cleared.
+    pub ns_per_byte_cleared: Option<f64>,
+}
+
+/// Summarize `results` per strategy, in the order strategies first appear.
+///
+/// When `bootstrap` is set, the median's confidence interval is estimated by
+/// resampling the per-iteration durations that many times.
+pub fn summarize(
+    results: &[BenchResult],
+    bootstrap: Option<usize>,
+    seed: u64,
+) -> Vec<StrategySummary> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut strategies: Vec<Strategy> = Vec::new();
+    for result in results {
+        if !strategies.contains(&result.strategy) {
+            strategies.push(result.strategy);
+        }
+    }
+
+    let page_size = rustix::param::page_size();
+    strategies
+        .into_iter()
+        .map(|strategy| {
+            let first = results.iter().find(|r| r.strategy == strategy).unwrap();
+            let dirty_bytes = (first.total_size as f64 * first.dirty_fraction).round() as usize;
+            let dirty_pages = dirty_bytes.div_ceil(page_size);
+
+            let mut samples: Vec<f64> = results
+                .iter()
+                .filter(|r| r.strategy == strategy)
+                .map(|r| r.duration.as_nanos() as f64)
+                .collect();
+            samples.sort_by(f64::total_cmp);
+
+            let median_ci =
+                bootstrap.map(|resamples| bootstrap_median_ci(&samples, resamples, &mut rng));
+
+            let optional_median = |f: fn(&BenchResult) -> Option<Duration>| {
+                let mut samples: Vec<f64> = results
+                    .iter()
+                    .filter(|r| r.strategy == strategy)
+                    .filter_map(f)
+                    .map(|d| d.as_nanos() as f64)
+                    .collect();
+                samples.sort_by(f64::total_cmp);
+                (!samples.is_empty()).then(|| nanos(percentile(&samples, 0.5)))
+            };
+
+            let rate_median = |f: fn(&BenchResult) -> Option<f64>| {
+                let mut samples: Vec<f64> = results
+                    .iter()
+                    .filter(|r| r.strategy == strategy)
+                    .filter_map(f)
+                    .collect();
+                samples.sort_by(f64::total_cmp);
+                (!samples.is_empty()).then(|| percentile(&samples, 0.5))
+            };
+
+            let mut background: Vec<f64> = results
+                .iter()
+                .filter(|r| r.strategy == strategy)
+                .filter_map(|r| r.background_duration)
+                .map(|d| d.as_nanos() as f64)
+                .collect();
+            background.sort_by(f64::total_cmp);
+            let background_ci = bootstrap
+                .filter(|_| !background.is_empty())
+                .map(|resamples| bootstrap_median_ci(&background, resamples, &mut rng));
+
+            let ns_per_byte_cleared = rate_median(|r| {
+                let cleared = r.cleared_bytes();
+                let clear = r.phases.clear?;
+                (cleared > 0).then(|| clear.as_nanos() as f64 / cleared as f64)
+            });
+
+            let median = percentile(&samples, 0.5);
+            let per = |count: usize| (count > 0).then(|| median / count as f64);
+
+            StrategySummary {
+                strategy,
+                samples: samples.len(),
+                min: nanos(samples[0]),
+                median: nanos(median),
+                mean: nanos(samples.iter().sum::<f64>() / samples.len() as f64),
+                max: nanos(samples[samples.len() - 1]),
+                median_ci,
+                background_median: optional_median(|r| r.background_duration),
+                background_ci,
+                cycle_median: optional_median(|r| r.cycle_duration),
+                scan_pages_per_sec: rate_median(|r| r.scan_pages_per_sec),
+                scan_regions_per_sec: rate_median(|r| r.scan_regions_per_sec),
+                ns_per_dirty_page: per(dirty_pages),
+                ns_per_byte_cleared,
+            }
+        })
+        .collect()
+}
+
+/// Which strategy had the lowest median, and whether its lead over the
+/// runner-up is significant. Strategies that clear in the background are
+/// ranked by when their clearing completed, since their foreground median
+/// leaves most of the work out; the margin and intervals are of that time.
+#[derive(Serialize, Debug)]
+pub struct Verdict {
+    pub best_strategy: Strategy,
+    pub runner_up: Strategy,
+
+    /// How much less time the best strategy's median took, as a percentage of
+    /// the runner-up's median.
+    pub margin_pct: f64,
+
+    /// Whether the two medians' 95% confidence intervals are disjoint. Always
+    /// false without `--bootstrap`, since there are no intervals to compare.
+    pub significant: bool,
+    pub best_ci: Option<(Duration, Duration)>,
+    pub runner_up_ci: Option<(Duration, Duration)>,
+    pub reasoning: String,
+}
+
+/// A strategy's median and its interval as the verdict ranks them.
+struct Ranked<'a> {
+    summary: &'a StrategySummary,
+    median: Duration,
+    ci: Option<(Duration, Duration)>,
+    label: &'static str,
+}
+
+impl<'a> Ranked<'a> {
+    fn new(summary: &'a StrategySummary) -> Self {
+        match summary.background_median {
+            Some(median) => Ranked {
+                summary,
+                median,
+                ci: summary.background_ci,
+                label: "completed clear median",
+            },
+            None => Ranked {
+                summary,
+                median: summary.median,
+                ci: summary.median_ci,
+                label: "median",
+            },
+        }
+    }
+}
+
+/// Compare the strategy with the lowest median against the next lowest, or
+/// `None` with fewer than two strategies.
+pub fn verdict(summaries: &[StrategySummary]) -> Option<Verdict> {
+    let mut ranked: Vec<Ranked> = summaries.iter().map(Ranked::new).collect();
+    ranked.sort_by_key(|r| r.median);
+    let (best, runner_up) = match &ranked[..] {
+        [best, runner_up, ..] => (best, runner_up),
+        _ => return None,
+    };
+
+    let margin_pct = (1.0 - micros(best.median) / micros(runner_up.median)) * 100.0;
+    let (significant, mut reasoning) = match (best.ci, runner_up.ci) {
+        (Some(best_ci), Some(runner_up_ci)) => {
+            let significant = best_ci.1 < runner_up_ci.0;
+            (
+                significant,
+                format!(
+                    "{:?} {} CI [{:.2}, {:.2}]us {} {:?} {} CI [{:.2}, {:.2}]us",
+                    best.summary.strategy,
+                    best.label,
+                    micros(best_ci.0),
+                    micros(best_ci.1),
+                    if significant {
+                        "lies entirely below"
+                    } else {
+                        "overlaps"
+                    },
+                    runner_up.summary.strategy,
+                    runner_up.label,
+                    micros(runner_up_ci.0),
+                    micros(runner_up_ci.1)
+                ),
+            )
+        }
+        _ => (
+            false,
+            format!(
+                "{:?} {} {:.2}us is below {:?} {} {:.2}us, but without --bootstrap \
+                 there are no confidence intervals to compare",
+                best.summary.strategy,
+                best.label,
+                micros(best.median),
+                runner_up.summary.strategy,
+                runner_up.label,
+                micros(runner_up.median)
+            ),
+        ),
+    };
+    let deferred: Vec<String> = [best, runner_up]
+        .into_iter()
+        .filter(|r| r.summary.background_median.is_some())
+        .map(|r| format!("{:?}", r.summary.strategy))
+        .collect();
+    if !deferred.is_empty() {
+        reasoning.push_str(&format!(
+            "; {} ranked by when background clearing completed, not foreground latency",
+            deferred.join(", ")
+        ));
+    }
+
+    Some(Verdict {
+        best_strategy: best.summary.strategy,
+        runner_up: runner_up.summary.strategy,
+        margin_pct,
+        significant,
+        best_ci: best.ci,
+        runner_up_ci: runner_up.ci,
+        reasoning,
+    })
+}
+
+pub fn print_verdict(verdict: &Verdict) {
+    println!(
+        "Best: {:?}, {:.1}% faster than {:?} ({})",
+        verdict.best_strategy,
+        verdict.margin_pct,
+        verdict.runner_up,
+        if verdict.significant {
+            "significant"
+        } else {
+            "not significant"
+        }
+    );
+    println!("  {}", verdict.reasoning);
+}
+
+/// Linearly interpolated percentile of an already sorted, non-empty slice.
+pub fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = p * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    let weight = rank - lower as f64;
+    sorted[lower] * (1.0 - weight) + sorted[upper] * weight
+}
+
+fn bootstrap_median_ci(sorted: &[f64], resamples: usize, rng: &mut StdRng) -> (Duration, Duration) {
+    let mut medians: Vec<f64> = Vec::with_capacity(resamples);
+    let mut resample = vec![0.0; sorted.len()];
+    for _ in 0..resamples.max(1) {
+        for slot in resample.iter_mut() {
+            *slot = sorted[rng.random_range(0..sorted.len())];
+        }
+        resample.sort_by(f64::total_cmp);
+        medians.push(percentile(&resample, 0.5));
+    }
+    medians.sort_by(f64::total_cmp);
+    (
+        nanos(percentile(&medians, 0.025)),
+        nanos(percentile(&medians, 0.975)),
+    )
+}
+
+fn nanos(ns: f64) -> Duration {
+    Duration::from_nanos(ns.round() as u64)
+}
+
+fn micros(d: Duration) -> f64 {
+    d.as_nanos() as f64 / 1000.0
+}
+
+pub fn print_summary(summaries: &[StrategySummary]) {
+    println!(
+        "{:<16} {:>8} {:>12} {:>12} {:>12} {:>12} {:>12} {:>12}  Median 95% CI (us)",
+        "Strategy",
+        "Samples",
+        "Min (us)",
+        "Median (us)",
+        "Mean (us)",
+        "Max (us)",
+        "ns/page",
+        "ns/byte"
+    );
+    for s in summaries {
+        let ci = match s.median_ci {
+            Some((low, high)) => format!("[{:.2}, {:.2}]", micros(low), micros(high)),
+            None => "-".to_string(),
+        };
+        let fmt_per = |per: Option<f64>| match per {
+            Some(per) => format!("{:.3}", per),
+            None => "-".to_string(),
+        };
+        println!(
+            "{:<16} {:>8} {:>12.2} {:>12.2} {:>12.2} {:>12.2} {:>12} {:>12}  {}",
+            format!("{:?}", s.strategy),
+            s.samples,
+            micros(s.min),
+            micros(s.median),
+            micros(s.mean),
+            micros(s.max),
+            fmt_per(s.ns_per_dirty_page),
+            fmt_per(s.ns_per_byte_cleared),
+            ci
+        );
+        for (label, median) in [
+            ("  (background)", s.background_median),
+            ("  (full cycle)", s.cycle_median),
+        ] {
+            if let Some(median) = median {
+                println!(
+                    "{:<16} {:>8} {:>12} {:>12.2}",
+                    label,
+                    "",
+                    "",
+                    micros(median)
+                );
+            }
+        }
+        if let (Some(pages), Some(regions)) = (s.scan_pages_per_sec, s.scan_regions_per_sec) {
+            println!(
+                "{:<16} {:>8} {:.1} Mpages/s, {:.3} Mregions/s",
+                "  (scan rate)",
+                "",
+                pages / 1e6,
+                regions / 1e6
+            );
+        }
+    }
+}
+
+/// Print an NxN matrix where cell (i, j) is the ratio of strategy i's median
+/// to strategy j's median.
+pub fn print_pairwise_matrix(summaries: &[StrategySummary]) {
+    print!("{:<16}", "Median ratio");
+    for s in summaries {
+        print!(" {:>14}", format!("{:?}", s.strategy));
+    }
+    println!();
+    for row in summaries {
+        print!("{:<16}", format!("{:?}", row.strategy));
+        for col in summaries {
+            let ratio = row.median.as_nanos() as f64 / col.median.as_nanos() as f64;
+            print!(" {:>14.3}", ratio);
+        }
+        println!();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{verdict, StrategySummary};
+    use crate::Strategy;
+    use std::time::Duration;
+
+    fn summary(strategy: Strategy, median_us: u64, background_us: Option<u64>) -> StrategySummary {
+        let median = Duration::from_micros(median_us);
+        StrategySummary {
+            strategy,
+            samples: 1,
+            min: median,
+            median,
+            mean: median,
+            max: median,
+            median_ci: None,
+            background_median: background_us.map(Duration::from_micros),
+            background_ci: None,
+            cycle_median: None,
+            scan_pages_per_sec: None,
+            scan_regions_per_sec: None,
+            ns_per_dirty_page: None,
+            ns_per_byte_cleared: None,
+        }
+    }
+
+    #[test]
+    fn verdict_notes_background_ranking_when_it_placed() {
+        let summaries = [
+            summary(Strategy::Madvise, 300, None),
+            summary(Strategy::BackgroundClear, 10, Some(200)),
+        ];
+        let verdict = verdict(&summaries).unwrap();
+        assert_eq!(verdict.best_strategy, Strategy::BackgroundClear);
+        assert!(verdict
+            .reasoning
+            .contains("BackgroundClear ranked by when background clearing completed"));
+    }
+
+    #[test]
+    fn verdict_omits_background_note_when_it_didnt_place() {
+        let summaries = [
+            summary(Strategy::Madvise, 300, None),
+            summary(Strategy::MemZero, 400, None),
+            summary(Strategy::BackgroundClear, 10, Some(900)),
+        ];
+        let verdict = verdict(&summaries).unwrap();
+        assert_eq!(verdict.best_strategy, Strategy::Madvise);
+        assert_eq!(verdict.runner_up, Strategy::MemZero);
+        assert!(!verdict.reasoning.contains("background"));
+    }
+}
@@ -0,0 +1,125 @@
+//! Dirty pages that have been swapped out, which `PAGEMAP_SCAN` reports as
+//! `PAGE_IS_SWAPPED` and which are slower to clear since writing them first
+//! reads them back from swap.
+
+use crate::pagemap::{self, PageCategories, ScanCategories};
+use crate::stats::percentile;
+use crate::{BenchArgs, MemoryRegion};
+use serde::Serialize;
+use std::slice;
+use std::time::{Duration, Instant};
+
+#[derive(Serialize, Debug)]
+pub struct SwapResult {
+    pub total_size: usize,
+    pub dirty_fraction: f64,
+    pub threads: usize,
+    pub processes: usize,
+
+    /// Time for MADV_PAGEOUT to push the dirty pages out.
+    pub pageout_duration: Duration,
+
+    /// Time to find the swapped dirty pages.
+    pub scan_duration: Duration,
+
+    /// Time to zero the swapped dirty pages, faulting them back in.
+    pub clear_duration: Duration,
+    pub regions: usize,
+    pub swapped_pages: usize,
+}
+
+pub fn run(args: &BenchArgs, iterations: u64) -> anyhow::Result<Vec<SwapResult>> {
+    let page_size = rustix::param::page_size();
+    let swapped = ScanCategories {
+        mask: args.scan_categories.mask | PageCategories::SWAPPED,
+        ..args.scan_categories
+    };
+
+    let mut region = MemoryRegion::new(args, false)?;
+    let mut regions = args.scan_buffer();
+    let results = (0..iterations)
+        .map(|_i| {
+            region.predirty(args)?;
+            region.make_dirty()?;
+            std::thread::sleep(args.age);
+
+            let start = Instant::now();
+            let ret = unsafe {
+                libc::madvise(
+                    region.ptr as *mut libc::c_void,
+                    args.total_size,
+                    libc::MADV_PAGEOUT,
+                )
+            };
+            let pageout_duration = start.elapsed();
+            if ret != 0 {
+                return Err(std::io::Error::last_os_error().into());
+            }
+
+            // Collect first so the scan and the swap-ins are timed apart.
+            let mut ranges = Vec::new();
+            let start = Instant::now();
+            pagemap::for_each_dirty_region(
+                region.ptr,
+                args.total_size,
+                regions.as_mut(),
+                &swapped,
+                args.max_scan_resumes,
+                |dirty_regions| {
+                    ranges.extend(dirty_regions.iter().map(|r| (r.start, r.end)));
+                    Ok(())
+                },
+            )?;
+            let scan_duration = start.elapsed();
+
+            let swapped_bytes: u64 = ranges.iter().map(|&(start, end)| end - start).sum();
+            if swapped_bytes == 0 {
+                return Err(anyhow::anyhow!(
+                    "MADV_PAGEOUT left no dirty page swapped out; is swap enabled?"
+                ));
+            }
+
+            let start = Instant::now();
+            for &(start, end) in &ranges {
+                let len = usize::try_from(end - start)?;
+                unsafe { slice::from_raw_parts_mut(start as *mut u8, len) }.fill(0);
+            }
+            let clear_duration = start.elapsed();
+
+            Ok(SwapResult {
+                total_size: args.total_size,
+                dirty_fraction: args.dirty_fraction,
+                threads: args.threads,
+                processes: args.processes,
+                pageout_duration,
+                scan_duration,
+                clear_duration,
+                regions: ranges.len(),
+                swapped_pages: swapped_bytes as usize / page_size,
+            })
+        })
+        .collect();
+    region.release(args);
+    results
+}
+
+pub fn print_summary(results: &[SwapResult]) {
+    let median = |f: fn(&SwapResult) -> Duration| {
+        let mut samples: Vec<f64> = results.iter().map(|r| f(r).as_nanos() as f64).collect();
+        samples.sort_by(f64::total_cmp);
+        percentile(&samples, 0.5) / 1000.0
+    };
+    let mean_swapped =
+        results.iter().map(|r| r.swapped_pages as f64).sum::<f64>() / results.len() as f64;
+    println!(
+        "{:>16} {:>16} {:>16} {:>14}",
+        "Pageout (us)", "Scan (us)", "Clear (us)", "Swapped pages"
+    );
+    println!(
+        "{:>16.2} {:>16.2} {:>16.2} {:>14.1}",
+        median(|r| r.pageout_duration),
+        median(|r| r.scan_duration),
+        median(|r| r.clear_duration),
+        mean_swapped
+    );
+}
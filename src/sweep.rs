@@ -0,0 +1,348 @@
+//! Sweeps that vary one property of the dirty set while holding the rest of the
+//! configuration fixed.
+
+use crate::stats::percentile;
+use crate::{pagemap, run_benchmark_pagemap_scan, BenchArgs, BenchResult, DirtyRuns, MemoryRegion};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Dirty run lengths, in pages, measured by [`run_lengths`].
+const RUN_LENGTHS: [usize; 9] = [1, 2, 4, 8, 16, 32, 64, 128, 256];
+
+#[derive(Serialize, Debug)]
+pub struct RunLengthPoint {
+    pub run_length: usize,
+    pub dirty_fraction: f64,
+    pub samples: usize,
+
+    /// Median time in the scan itself, excluding dirtying and clearing.
+    pub scan_median: Duration,
+
+    /// Median time of the whole dirty, scan and clear operation.
+    pub duration_median: Duration,
+
+    /// Dirty regions the scan reported.
+    pub regions: usize,
+}
+
+/// Measure `PAGEMAP_SCAN` as the dirty set goes from single-page runs to long
+/// runs at the same dirty fraction, isolating the cost of fragmentation.
+pub fn run_lengths(args: &BenchArgs, iterations: u64) -> anyhow::Result<Vec<RunLengthPoint>> {
+    RUN_LENGTHS
+        .iter()
+        .map(|&run_length| {
+            let args = BenchArgs {
+                dirty_run: Some(run_length),
+                ..args.clone()
+            };
+            let mut region = MemoryRegion::new(&args, false)?;
+            let results = (0..iterations)
+                .map(|_i| run_benchmark_pagemap_scan(&args, &mut region))
+                .collect::<anyhow::Result<Vec<BenchResult>>>()?;
+            region.release(&args);
+
+            let median = |f: fn(&BenchResult) -> Duration| {
+                let mut samples: Vec<f64> =
+                    results.iter().map(|r| f(r).as_nanos() as f64).collect();
+                samples.sort_by(f64::total_cmp);
+                Duration::from_nanos(percentile(&samples, 0.5).round() as u64)
+            };
+            Ok(RunLengthPoint {
+                run_length,
+                dirty_fraction: args.dirty_fraction,
+                samples: results.len(),
+                scan_median: median(|r| r.phases.scan.unwrap_or_default()),
+                duration_median: median(|r| r.duration),
+                regions: results
+                    .iter()
+                    .filter_map(|r| r.scan_regions)
+                    .max()
+                    .unwrap_or(0),
+            })
+        })
+        .collect()
+}
+
+/// Pages per clean gap measured by [`gaps`], from sparse gaps to every other
+/// page.
+const GAP_PERIODS: [usize; 10] = [1024, 512, 256, 128, 64, 32, 16, 8, 4, 2];
+
+#[derive(Serialize, Debug)]
+pub struct GapPoint {
+    /// One page in every `gap_period` is left clean.
+    pub gap_period: usize,
+    pub gaps: usize,
+
+    /// Regions the scan reported. Every dirty run ends at a gap, so this equals
+    /// `gaps` if the scan coalesces each run into one region.
+    pub regions: usize,
+    pub scan_median: Duration,
+}
+
+#[derive(Serialize, Debug)]
+pub struct GapSweep {
+    pub points: Vec<GapPoint>,
+
+    /// The most regions any scan reported, which a scan buffer needs to hold
+    /// to avoid resuming.
+    pub max_regions: usize,
+}
+
+/// Dirty the whole region but for one clean page in every `gap_period`, and
+/// count the regions the scan splits it into as the gaps get denser.
+pub fn gaps(args: &BenchArgs, iterations: u64) -> anyhow::Result<GapSweep> {
+    let page_size = rustix::param::page_size();
+    let pages = args.total_size.div_ceil(page_size);
+    let mut regions = args.scan_buffer();
+    let points = GAP_PERIODS
+        .iter()
+        .map(|&gap_period| {
+            let mut region = MemoryRegion::new(args, false)?;
+            region.dirty_runs = Some(DirtyRuns {
+                run: gap_period - 1,
+                period: gap_period,
+            });
+            region.dirty_prefix(args.total_size)?;
+
+            let mut region_count = 0;
+            let mut durations = (0..iterations.max(1))
+                .map(|_i| {
+                    let start = Instant::now();
+                    region_count = 0;
+                    pagemap::for_each_dirty_region(
+                        region.ptr,
+                        args.total_size,
+                        &mut regions,
+                        &args.scan_categories,
+                        args.max_scan_resumes,
+                        |dirty_regions| {
+                            region_count += dirty_regions.len();
+                            Ok(())
+                        },
+                    )?;
+                    Ok(start.elapsed().as_nanos() as f64)
+                })
+                .collect::<anyhow::Result<Vec<f64>>>()?;
+            durations.sort_by(f64::total_cmp);
+            region.release(args);
+
+            Ok(GapPoint {
+                gap_period,
+                gaps: pages / gap_period,
+                regions: region_count,
+                scan_median: Duration::from_nanos(percentile(&durations, 0.5).round() as u64),
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let max_regions = points.iter().map(|p| p.regions).max().unwrap_or(0);
+    Ok(GapSweep {
+        points,
+        max_regions,
+    })
+}
+
+pub fn print_gaps(sweep: &GapSweep) {
+    println!(
+        "{:>12} {:>10} {:>10} {:>16}",
+        "Gap period", "Gaps", "Regions", "Scan median (us)"
+    );
+    for p in &sweep.points {
+        println!(
+            "{:>12} {:>10} {:>10} {:>16.2}",
+            p.gap_period,
+            p.gaps,
+            p.regions,
+            p.scan_median.as_nanos() as f64 / 1000.0
+        );
+    }
+    println!("\nMax regions per scan: {}", sweep.max_regions);
+}
+
+/// Scan intervals, in milliseconds, measured by [`scan_intervals`].
+const SCAN_INTERVALS_MS: [u64; 7] = [1, 2, 5, 10, 20, 50, 100];
+
+#[derive(Serialize, Debug)]
+pub struct ScanIntervalPoint {
+    pub interval: Duration,
+
+    /// Pages per millisecond the background writer dirties.
+    pub dirty_rate: f64,
+    pub scans: usize,
+
+    /// Wall time over which the scans kept up with the writer.
+    pub tracked: Duration,
+
+    /// Total time spent scanning and clearing, across all scans.
+    pub scan_duration: Duration,
+    pub clear_duration: Duration,
+
+    /// Mean dirty pages found per scan.
+    pub dirty_pages: f64,
+
+    /// Scan plus clear time per millisecond tracked.
+    pub overhead: f64,
+}
+
+/// Keep dirtying random pages at `dirty_rate` pages per millisecond from a
+/// background thread while scanning for and clearing them every interval, to
+/// find the interval at which tracking costs least.
+///
+/// Each scan reports the pages written since the previous one, which are then
+/// cleared with MADV_DONTNEED so the next scan only sees new writes.
+pub fn scan_intervals(
+    args: &BenchArgs,
+    iterations: u64,
+    dirty_rate: f64,
+) -> anyhow::Result<Vec<ScanIntervalPoint>> {
+    let page_size = rustix::param::page_size();
+    let region = MemoryRegion::new(args, false)?;
+    let mut regions = args.scan_buffer();
+
+    // The writer can't borrow the region, so hand it the addresses to write.
+    let base = region.ptr as usize;
+    let writable: Vec<usize> = (0..args.total_size / page_size)
+        .filter(|&page| !region.is_guard_page(page))
+        .map(|page| base + page * page_size)
+        .collect();
+    if writable.is_empty() {
+        return Err(anyhow::anyhow!("No writable pages to dirty"));
+    }
+
+    let mut scan_and_clear = |region: &MemoryRegion| -> anyhow::Result<(Duration, Duration, u64)> {
+        let mut ranges = Vec::new();
+        let start = Instant::now();
+        pagemap::for_each_dirty_region(
+            region.ptr,
+            args.total_size,
+            &mut regions,
+            &args.scan_categories,
+            args.max_scan_resumes,
+            |dirty_regions| {
+                ranges.extend(dirty_regions.iter().map(|r| (r.start, r.end)));
+                Ok(())
+            },
+        )?;
+        let scanned = Instant::now();
+        let mut dirty = 0;
+        for (start, end) in ranges {
+            let ret = unsafe {
+                libc::madvise(
+                    start as *mut libc::c_void,
+                    usize::try_from(end - start)?,
+                    libc::MADV_DONTNEED,
+                )
+            };
+            if ret != 0 {
+                return Err(std::io::Error::last_os_error().into());
+            }
+            dirty += end - start;
+        }
+        Ok((scanned - start, scanned.elapsed(), dirty / page_size as u64))
+    };
+
+    let points = SCAN_INTERVALS_MS
+        .iter()
+        .map(|&interval_ms| {
+            let interval = Duration::from_millis(interval_ms);
+            // Start from an empty dirty set.
+            scan_and_clear(&region)?;
+
+            let stop = AtomicBool::new(false);
+            thread::scope(|s| {
+                let writer = s.spawn(|| {
+                    let mut rng = StdRng::seed_from_u64(args.seed);
+                    let start = Instant::now();
+                    let mut written = 0u64;
+                    while !stop.load(Ordering::Relaxed) {
+                        let due = (start.elapsed().as_secs_f64() * 1000.0 * dirty_rate) as u64;
+                        for _ in written..due {
+                            let addr = writable[rng.random_range(0..writable.len())];
+                            unsafe { std::ptr::write_volatile(addr as *mut u8, 1) };
+                        }
+                        written = written.max(due);
+                        thread::sleep(Duration::from_micros(50));
+                    }
+                });
+
+                let start = Instant::now();
+                let mut scan_duration = Duration::ZERO;
+                let mut clear_duration = Duration::ZERO;
+                let mut dirty_pages = 0;
+                let scans = (1..=iterations).try_for_each(|i| {
+                    let deadline = start + interval * i as u32;
+                    thread::sleep(deadline.saturating_duration_since(Instant::now()));
+                    let (scan, clear, dirty) = scan_and_clear(&region)?;
+                    scan_duration += scan;
+                    clear_duration += clear;
+                    dirty_pages += dirty;
+                    anyhow::Ok(())
+                });
+                let tracked = start.elapsed();
+                stop.store(true, Ordering::Relaxed);
+                let _ = writer.join();
+                scans?;
+
+                let tracked_ms = tracked.as_secs_f64() * 1000.0;
+                Ok(ScanIntervalPoint {
+                    interval,
+                    dirty_rate,
+                    scans: iterations as usize,
+                    tracked,
+                    scan_duration,
+                    clear_duration,
+                    dirty_pages: dirty_pages as f64 / iterations as f64,
+                    overhead: (scan_duration + clear_duration).as_secs_f64() * 1000.0 / tracked_ms,
+                })
+            })
+        })
+        .collect();
+    region.release(args);
+    points
+}
+
+pub fn print_scan_intervals(points: &[ScanIntervalPoint]) {
+    println!(
+        "{:>14} {:>12} {:>14} {:>15} {:>16}",
+        "Interval (ms)", "Dirty pages", "Scan/scan (us)", "Clear/scan (us)", "Overhead (us/ms)"
+    );
+    for p in points {
+        let per_scan = |d: Duration| d.as_nanos() as f64 / 1000.0 / p.scans as f64;
+        println!(
+            "{:>14} {:>12.1} {:>14.2} {:>15.2} {:>16.2}",
+            p.interval.as_millis(),
+            p.dirty_pages,
+            per_scan(p.scan_duration),
+            per_scan(p.clear_duration),
+            p.overhead * 1000.0
+        );
+    }
+    if let Some(best) = points
+        .iter()
+        .min_by(|a, b| a.overhead.total_cmp(&b.overhead))
+    {
+        println!(
+            "\nLowest overhead at a {}ms interval",
+            best.interval.as_millis()
+        );
+    }
+}
+
+pub fn print_run_lengths(points: &[RunLengthPoint]) {
+    println!(
+        "{:>12} {:>16} {:>17} {:>10}",
+        "Run (pages)", "Scan median (us)", "Total median (us)", "Regions"
+    );
+    for p in points {
+        println!(
+            "{:>12} {:>16.2} {:>17.2} {:>10}",
+            p.run_length,
+            p.scan_median.as_nanos() as f64 / 1000.0,
+            p.duration_median.as_nanos() as f64 / 1000.0,
+            p.regions
+        );
+    }
+}
@@ -0,0 +1,172 @@
+//! `MADV_DONTNEED` on transparent huge pages, with ranges aligned to huge page
+//! boundaries (dropping whole huge pages) or deliberately misaligned (forcing
+//! the kernel to split them first).
+
+use crate::meminfo::{self, ThpUsage};
+use crate::pagemap::{self, ScopedMapping};
+use crate::stats::percentile;
+use nix::sys::mman::{madvise, MmapAdvise};
+use serde::Serialize;
+use std::ptr::NonNull;
+use std::time::{Duration, Instant};
+
+#[derive(Serialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Alignment {
+    /// Each range covers exactly one huge page.
+    Aligned,
+    /// Each range is shifted by half a huge page, straddling two of them.
+    Misaligned,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ThpMadviseResult {
+    pub alignment: Alignment,
+    pub total_size: usize,
+    pub huge_page_size: usize,
+    pub ranges: usize,
+    pub duration: Duration,
+
+    /// Huge pages backing the region before the madvise.
+    pub huge_pages: usize,
+    pub huge_pages_split: usize,
+}
+
+pub fn run(total_size: usize, iterations: u64) -> anyhow::Result<Vec<ThpMadviseResult>> {
+    let huge_page_size = meminfo::thp_size();
+    let size = total_size.next_multiple_of(huge_page_size);
+    // Room for one range of each alignment.
+    let min_size = 2 * huge_page_size;
+    if size < min_size {
+        return Err(anyhow::anyhow!(
+            "--thp-madvise-alignment needs a size of at least {} bytes (two huge pages), got {}",
+            min_size,
+            total_size
+        ));
+    }
+    // Over-allocated so the huge page alignment can be chosen by hand.
+    pagemap::scan_scope(size + huge_page_size, |mapping| {
+        run_in(mapping, size, huge_page_size, iterations)
+    })?
+}
+
+fn run_in(
+    mapping: &mut ScopedMapping,
+    size: usize,
+    huge_page_size: usize,
+    iterations: u64,
+) -> anyhow::Result<Vec<ThpMadviseResult>> {
+    let map = NonNull::new(mapping.as_ptr())
+        .ok_or_else(|| anyhow::anyhow!("null mapping"))?
+        .cast();
+    unsafe { madvise(map, mapping.len(), MmapAdvise::MADV_HUGEPAGE) }?;
+    let offset =
+        (mapping.as_ptr() as usize).next_multiple_of(huge_page_size) - mapping.as_ptr() as usize;
+    let region = &mut mapping.as_mut_slice()[offset..offset + size];
+    let base = region.as_mut_ptr();
+
+    let mut results = Vec::new();
+    for alignment in [Alignment::Aligned, Alignment::Misaligned] {
+        let starts = range_starts(alignment, size, huge_page_size);
+
+        for _ in 0..iterations {
+            region.fill(0xAA);
+            let before = ThpUsage::sample()?;
+            let huge_pages = before.anon_huge_pages(huge_page_size);
+            if huge_pages == 0 {
+                return Err(anyhow::anyhow!(
+                    "no transparent huge pages were allocated; is THP enabled?"
+                ));
+            }
+
+            let start = Instant::now();
+            for &range_start in &starts {
+                let addr = NonNull::new(base.wrapping_add(range_start))
+                    .ok_or_else(|| anyhow::anyhow!("null madvise address"))?;
+                unsafe { madvise(addr.cast(), huge_page_size, MmapAdvise::MADV_DONTNEED) }?;
+            }
+            let duration = start.elapsed();
+
+            results.push(ThpMadviseResult {
+                alignment,
+                total_size: size,
+                huge_page_size,
+                ranges: starts.len(),
+                duration,
+                huge_pages,
+                huge_pages_split: before.pages_split_since(&ThpUsage::sample()?),
+            });
+        }
+    }
+    Ok(results)
+}
+
+/// Offsets of the huge page sized ranges to madvise in a `size` byte region,
+/// which must hold at least two huge pages. Every other huge page, so
+/// misaligned ranges never merge and the last one still fits in the region.
+fn range_starts(alignment: Alignment, size: usize, huge_page_size: usize) -> Vec<usize> {
+    let offset = match alignment {
+        Alignment::Aligned => 0,
+        Alignment::Misaligned => huge_page_size / 2,
+    };
+    (offset..size - huge_page_size)
+        .step_by(2 * huge_page_size)
+        .collect()
+}
+
+pub fn print_summary(results: &[ThpMadviseResult]) {
+    println!(
+        "{:<12} {:>8} {:>14} {:>12} {:>12}",
+        "Alignment", "Ranges", "Median (us)", "Huge pages", "Split"
+    );
+    for alignment in [Alignment::Aligned, Alignment::Misaligned] {
+        let runs: Vec<&ThpMadviseResult> = results
+            .iter()
+            .filter(|r| r.alignment == alignment)
+            .collect();
+        let Some(first) = runs.first() else {
+            continue;
+        };
+        let mut durations: Vec<f64> = runs.iter().map(|r| r.duration.as_nanos() as f64).collect();
+        durations.sort_by(f64::total_cmp);
+        let mean = |f: fn(&ThpMadviseResult) -> usize| {
+            runs.iter().map(|r| f(r) as f64).sum::<f64>() / runs.len() as f64
+        };
+        println!(
+            "{:<12} {:>8} {:>14.2} {:>12.1} {:>12.1}",
+            format!("{:?}", alignment),
+            first.ranges,
+            percentile(&durations, 0.5) / 1000.0,
+            mean(|r| r.huge_pages),
+            mean(|r| r.huge_pages_split)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{range_starts, run, Alignment};
+    use crate::meminfo;
+
+    #[test]
+    fn run_rejects_sizes_below_two_huge_pages() {
+        let huge_page_size = meminfo::thp_size();
+        for size in [0, huge_page_size] {
+            let err = run(size, 1).unwrap_err().to_string();
+            assert!(err.contains(&(2 * huge_page_size).to_string()), "{}", err);
+        }
+    }
+
+    #[test]
+    fn two_huge_pages_hold_one_range_of_each_alignment() {
+        let huge_page_size = 2 << 20;
+        assert_eq!(
+            range_starts(Alignment::Aligned, 2 * huge_page_size, huge_page_size),
+            [0]
+        );
+        assert_eq!(
+            range_starts(Alignment::Misaligned, 2 * huge_page_size, huge_page_size),
+            [huge_page_size / 2]
+        );
+    }
+}
@@ -0,0 +1,35 @@
+//! "Tidy" long-format CSV output with one row per measured phase of each
+//! iteration, for grouping and plotting without reshaping.
+
+use crate::BenchResult;
+use std::io::Write;
+use std::time::Duration;
+
+pub fn write_tidy(mut out: impl Write, results: &[BenchResult]) -> anyhow::Result<()> {
+    writeln!(out, "strategy,thread,iteration,phase,duration_ns")?;
+    for r in results {
+        let phases: [(&str, Option<Duration>); 6] = [
+            ("map", r.phases.map),
+            ("dirty", r.phases.dirty),
+            ("scan", r.phases.scan),
+            ("clear", r.phases.clear),
+            ("refault", r.phases.refault),
+            ("unmap", r.phases.unmap),
+        ];
+        // Strategies only report the phases they actually have.
+        for (phase, duration) in phases {
+            if let Some(duration) = duration {
+                writeln!(
+                    out,
+                    "{:?},{},{},{},{}",
+                    r.strategy,
+                    r.thread,
+                    r.iteration,
+                    phase,
+                    duration.as_nanos()
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
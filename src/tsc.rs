@@ -0,0 +1,65 @@
+//! Tick counts from the time stamp counter. An invariant TSC ticks at a fixed
+//! nominal rate whatever the core's current frequency, so its ticks are not
+//! core cycles: a count is wall time times a per-machine constant, with a
+//! cheaper and finer grained read than the clock. It doesn't adjust for
+//! frequency scaling, and counts from machines with different TSC rates don't
+//! compare.
+
+/// Whether the CPU advertises an invariant TSC (CPUID leaf 0x8000_0007, EDX
+/// bit 8). Always false off x86_64.
+pub fn has_invariant_tsc() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        use std::arch::x86_64::__cpuid;
+        let max_extended_leaf = __cpuid(0x8000_0000).eax;
+        max_extended_leaf >= 0x8000_0007 && __cpuid(0x8000_0007).edx & (1 << 8) != 0
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        false
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn rdtsc() -> u64 {
+    unsafe { std::arch::x86_64::_rdtsc() }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn rdtsc() -> u64 {
+    unreachable!("--cycles requires an invariant TSC")
+}
+
+/// TSC ticks accumulated over a strategy's measured spans, started and stopped
+/// alongside the `Instant`s its duration is computed from.
+pub struct Cycles {
+    total: Option<u64>,
+    from: u64,
+}
+
+impl Cycles {
+    /// Counts nothing unless `enabled`.
+    pub fn new(enabled: bool) -> Self {
+        Cycles {
+            total: enabled.then_some(0),
+            from: 0,
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if self.total.is_some() {
+            self.from = rdtsc();
+        }
+    }
+
+    pub fn pause(&mut self) {
+        if let Some(total) = &mut self.total {
+            *total += rdtsc().wrapping_sub(self.from);
+        }
+    }
+
+    /// The ticks counted, or `None` if counting wasn't enabled.
+    pub fn total(&self) -> Option<u64> {
+        self.total
+    }
+}
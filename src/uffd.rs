@@ -0,0 +1,76 @@
+//! Minimal userfaultfd support for asynchronous write-protect (uffd-wp) dirty
+//! tracking, which `PAGEMAP_SCAN` can use to report and re-arm only the pages
+//! written since the previous scan.
+
+use rustix::fd::OwnedFd;
+use rustix::ioctl::{ioctl, opcode, Opcode, Updater};
+use rustix::mm::{userfaultfd, UserfaultfdFlags};
+
+const UFFD_API: u64 = 0xAA;
+const UFFD_USER_MODE_ONLY: u32 = 1;
+const UFFD_FEATURE_WP_UNPOPULATED: u64 = 1 << 13;
+const UFFD_FEATURE_WP_ASYNC: u64 = 1 << 15;
+const UFFDIO_REGISTER_MODE_WP: u64 = 1 << 1;
+
+#[repr(C)]
+struct uffdio_api {
+    api: u64,
+    features: u64,
+    ioctls: u64,
+}
+
+#[repr(C)]
+struct uffdio_range {
+    start: u64,
+    len: u64,
+}
+
+#[repr(C)]
+struct uffdio_register {
+    range: uffdio_range,
+    mode: u64,
+    ioctls: u64,
+}
+
+const UFFDIO_API: Opcode = opcode::read_write::<uffdio_api>(0xAA, 0x3F);
+const UFFDIO_REGISTER: Opcode = opcode::read_write::<uffdio_register>(0xAA, 0x00);
+
+/// A region registered for asynchronous write-protect tracking. Writes to the
+/// region never fault to userspace; the kernel just clears the page's
+/// write-protect bit, which `PAGEMAP_SCAN` reports as `PAGE_IS_WRITTEN`.
+///
+/// Dropping this closes the userfaultfd, which unregisters the region.
+pub struct WriteProtect {
+    _uffd: OwnedFd,
+}
+
+impl WriteProtect {
+    pub fn register(base: *const u8, len: usize) -> anyhow::Result<Self> {
+        let flags = UserfaultfdFlags::CLOEXEC
+            | UserfaultfdFlags::NONBLOCK
+            | UserfaultfdFlags::from_bits_retain(UFFD_USER_MODE_ONLY);
+        let uffd = unsafe { userfaultfd(flags) }
+            .map_err(|e| anyhow::anyhow!("userfaultfd unavailable: {}", e))?;
+
+        let mut api = uffdio_api {
+            api: UFFD_API,
+            features: UFFD_FEATURE_WP_ASYNC | UFFD_FEATURE_WP_UNPOPULATED,
+            ioctls: 0,
+        };
+        unsafe { ioctl(&uffd, Updater::<UFFDIO_API, _>::new(&mut api)) }
+            .map_err(|e| anyhow::anyhow!("async uffd-wp not supported: {}", e))?;
+
+        let mut register = uffdio_register {
+            range: uffdio_range {
+                start: base as u64,
+                len: len as u64,
+            },
+            mode: UFFDIO_REGISTER_MODE_WP,
+            ioctls: 0,
+        };
+        unsafe { ioctl(&uffd, Updater::<UFFDIO_REGISTER, _>::new(&mut register)) }
+            .map_err(|e| anyhow::anyhow!("UFFDIO_REGISTER failed: {}", e))?;
+
+        Ok(WriteProtect { _uffd: uffd })
+    }
+}